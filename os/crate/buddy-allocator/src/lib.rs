@@ -1,141 +1,120 @@
-
+#![cfg_attr(not(test), no_std)]
 #![feature(alloc)]
-#![no_std]
-#![feature(lang_items)]
+#![cfg_attr(not(test), feature(lang_items))]
 
 extern crate alloc;
-use alloc::vec::Vec;
+use alloc::{ collections::BTreeSet, vec::Vec };
+#[cfg(not(test))]
 use core::alloc::Layout;
 
+#[cfg(test)]
+mod tests;
+
+// 块的最大阶数，最大的块包含 2^(MAX_ORDER - 1) 页
+const MAX_ORDER: usize = 32;
+
+// 管理若干段页号范围的伙伴系统，每一阶各有一个空闲链表
+// 阶为 k 的块包含 2^k 页，且起始页号按 2^k 对齐
 pub struct BuddyAllocator {
-    nodes : Vec<i8>,
-    level : u8,
+    free_lists : Vec<BTreeSet<usize>>,  // 每一阶空闲块的起始页号，按地址排序
+    total : usize,  // 管理的总页数
+    allocated : usize,  // 已经分配出去的页数，按块的实际大小计算
 }
 
 impl BuddyAllocator {
     pub fn new() -> Self {
-        let ret = BuddyAllocator{
-            nodes : Vec::new(),
-            level : 0,
-        };
-        ret
+        let mut free_lists = Vec::with_capacity(MAX_ORDER);
+        for _ in 0..MAX_ORDER {
+            free_lists.push(BTreeSet::new());
+        }
+        BuddyAllocator{
+            free_lists,
+            total : 0,
+            allocated : 0,
+        }
     }
 
-    pub fn init(&mut self, level : u8) {
-        self.level = level;
-        let mut idx = 0;
-        for s in 0..level {
-            for _t in 0..(1 << s) {
-                self.nodes.push(0_i8);
-                self.nodes[idx] = ( level - s - 1 ) as i8;
-                idx = idx + 1;
-            }
+    // 加入页号范围 [start, end) 的空闲页，切成尽可能大的对齐的块。可以多次调用加入不连续的范围
+    pub fn add_region(&mut self, start : usize, end : usize) {
+        assert!(start <= end, "invalid region");
+        let mut current = start;
+        while current < end {
+            let align = if current == 0 { MAX_ORDER - 1 } else { current.trailing_zeros() as usize };
+            let order = align.min(log2_down(end - current)).min(MAX_ORDER - 1);
+            self.free_lists[order].insert(current);
+            current += 1 << order;
         }
+        self.total += end - start;
     }
 
-    pub fn alloc(&mut self, alloc_size : usize) -> Option<usize> {
-        let size = log2_up(alloc_size) as i8;
-        let mut location = 0;
-        let mut height = self.level - 1;
-        let ret;
-        while height != 0 {
-            if size > self.nodes[location] {
-                panic!("memory is not enough ");
-            }
-            if self.nodes[(location << 1) + 1] >= size {
-                location = (location << 1) + 1;
-            }else if self.nodes[(location + 1) << 1] >= size{
-                location = (location + 1) << 1;
-            }else{
-                break;
-            }
-            height = height - 1;
+    // 分配 size 页，实际分配的块大小为 size 向上取整到 2 的幂，起始页号按块大小对齐
+    // 没有足够大的空闲块或 size 为 0 时返回 None
+    pub fn alloc(&mut self, size : usize) -> Option<usize> {
+        if size == 0 {
+            return None;
         }
-        ret = (location + 1) * (1 << height) - (1 << (self.level - 1));
-        self.nodes[location] = -1;
-        while location > 0 {    // 回溯，更新父辈节点
-            if location & 0x1 > 0 { // 当前节点的下标为奇数
-                if self.nodes[location] > self.nodes[location + 1] {    // 当前节点的值大于兄弟节点的值
-                    self.nodes[location >> 1] = self.nodes[location];
-                }else{ // 兄弟节点的值不小于当前节点的值
-                    self.nodes[location >> 1] = self.nodes[location + 1];
-                }
-                location = location >> 1;
-            }else{  //当前节点的下标为偶数
-                if self.nodes[location] > self.nodes[location - 1] {
-                    self.nodes[(location - 1) >> 1] = self.nodes[location];
-                }else{
-                    self.nodes[(location - 1) >> 1] = self.nodes[location - 1];
-                }
-                location = (location - 1) >> 1;
-            }
+        let order = log2_up(size);
+        if order >= MAX_ORDER {
+            return None;
+        }
+        // 找到不小于所需大小的最小空闲块
+        let found = (order..MAX_ORDER).find(|&i| !self.free_lists[i].is_empty())?;
+        let block = *self.free_lists[found].iter().next().unwrap();
+        self.free_lists[found].remove(&block);
+        // 把多余的部分逐次对半拆开，高地址的一半放回空闲链表
+        for i in (order..found).rev() {
+            self.free_lists[i].insert(block + (1 << i));
         }
-        Some(ret)
+        self.allocated += 1 << order;
+        Some(block)
     }
 
-    pub fn dealloc(&mut self, address : usize, dealloc_size : usize){
-        let size = log2_down(dealloc_size) as i8;
-        let mut location = address + (1 << (self.level - 1)) - 1;
-        let mut height = 0_i8;
-        while size > height {
-            height += 1;
-            location = if location & 0x1 == 0 {
-                (location - 1) >> 1
-            }else{
-                location >> 1
-            };
-        }
-        self.nodes[location] = size as i8;
-        while location > 0 {
-            if location & 0x1 > 0 { //　奇数下标
-                if self.nodes[location] == self.nodes[location + 1] && self.nodes[location] == height{
-                    self.nodes[location >> 1] = self.nodes[location] + 1;
-                }else if self.nodes[location] > self.nodes[location >> 1] {
-                    self.nodes[location >> 1] = self.nodes[location];
-                }
-                location = location >> 1;
-            }else{ // 偶数下标
-                if self.nodes[location] == self.nodes[location - 1] && self.nodes[location] == height{
-                    self.nodes[(location - 1) >> 1] = self.nodes[location] + 1;
-                }else if self.nodes[location] > self.nodes[(location - 1) >> 1]{
-                    self.nodes[(location - 1) >> 1] = self.nodes[location];
-                }
-                location = (location - 1) >> 1;
+    // 释放从 start 开始的 size 页，size 需要与分配时相同。会与空闲的伙伴块合并
+    pub fn dealloc(&mut self, start : usize, size : usize) {
+        let mut order = log2_up(size);
+        assert_eq!(start & ((1 << order) - 1), 0, "dealloc: block is not aligned");
+        let mut block = start;
+        while order < MAX_ORDER - 1 {
+            let buddy = block ^ (1 << order);
+            if !self.free_lists[order].remove(&buddy) {
+                break;
             }
-            height = height + 1;
+            block = block.min(buddy);
+            order += 1;
         }
+        let inserted = self.free_lists[order].insert(block);
+        assert!(inserted, "dealloc: double free");
+        self.allocated -= 1 << log2_up(size);
     }
-}
 
+    pub fn total_frames(&self) -> usize {
+        self.total
+    }
 
-#[inline(always)]
-fn log2_up(x: usize) -> usize {    // 以２为底的对数向上取整的值，主要考虑分配内存时应该向上取整
-    assert_ne!(x, 0);
-    let mut temp_x = x;
-    let mut pos = -1;
-    while temp_x != 0 {
-        pos += 1;
-        temp_x >>= 1;
+    pub fn used_frames(&self) -> usize {
+        self.allocated
     }
-    if x - (1 << pos) != 0 {
-        pos = pos + 1;
+
+    pub fn free_frames(&self) -> usize {
+        self.total - self.allocated
     }
-    pos as usize
 }
 
 #[inline(always)]
-pub fn log2_down(x: usize) -> usize {    // 以２为底的对数向下取整的值,释放内存时向下取整
+pub fn log2_up(x: usize) -> usize {    // 以２为底的对数向上取整的值，主要考虑分配内存时应该向上取整
     assert_ne!(x, 0);
-    let mut temp_x = x;
-    let mut pos = -1;
-    while temp_x != 0 {
-        pos += 1;
-        temp_x >>= 1;
-    }
-    pos as usize
+    let down = log2_down(x);
+    if x == 1 << down { down } else { down + 1 }
+}
+
+#[inline(always)]
+pub fn log2_down(x: usize) -> usize {    // 以２为底的对数向下取整的值
+    assert_ne!(x, 0);
+    (core::mem::size_of::<usize>() * 8 - 1) - x.leading_zeros() as usize
 }
 
+#[cfg(not(test))]
 #[lang = "oom"]
 fn oom(_: Layout) -> ! {
     panic!("out of memory");
-} 
\ No newline at end of file
+}
@@ -0,0 +1,165 @@
+use crate::*;
+use std::collections::BTreeMap;
+
+// 简单的 xorshift 伪随机数生成器，保证每次运行的结果相同
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+#[test]
+fn log2() {
+    assert_eq!(log2_down(1), 0);
+    assert_eq!(log2_down(5), 2);
+    assert_eq!(log2_down(8), 3);
+    assert_eq!(log2_up(1), 0);
+    assert_eq!(log2_up(5), 3);
+    assert_eq!(log2_up(8), 3);
+}
+
+#[test]
+fn alloc_all_frames() {
+    let mut allocator = BuddyAllocator::new();
+    // 不是 2 的幂的大小也能全部使用
+    allocator.add_region(0x80000, 0x80000 + 1000);
+    assert_eq!(allocator.total_frames(), 1000);
+    let mut frames = Vec::new();
+    while let Some(frame) = allocator.alloc(1) {
+        assert!(frame >= 0x80000 && frame < 0x80000 + 1000);
+        frames.push(frame);
+    }
+    assert_eq!(frames.len(), 1000);
+    assert_eq!(allocator.free_frames(), 0);
+    for frame in frames {
+        allocator.dealloc(frame, 1);
+    }
+    assert_eq!(allocator.free_frames(), 1000);
+    assert_eq!(allocator.used_frames(), 0);
+}
+
+#[test]
+fn exhaustion_returns_none() {
+    let mut allocator = BuddyAllocator::new();
+    allocator.add_region(0, 16);
+    assert_eq!(allocator.alloc(32), None);
+    assert_eq!(allocator.alloc(16), Some(0));
+    assert_eq!(allocator.alloc(1), None);
+    allocator.dealloc(0, 16);
+    assert_eq!(allocator.alloc(1), Some(0));
+}
+
+#[test]
+fn zero_size_returns_none() {
+    let mut allocator = BuddyAllocator::new();
+    allocator.add_region(0, 16);
+    assert_eq!(allocator.alloc(0), None);
+    assert_eq!(allocator.used_frames(), 0);
+    assert_eq!(allocator.alloc(1), Some(0));
+}
+
+#[test]
+fn blocks_are_aligned() {
+    let mut allocator = BuddyAllocator::new();
+    allocator.add_region(3, 200);
+    for &size in [1, 2, 3, 4, 8, 16, 5].iter() {
+        let frame = allocator.alloc(size).unwrap();
+        assert_eq!(frame % size.next_power_of_two(), 0);
+    }
+}
+
+#[test]
+fn coalesce_after_free() {
+    let mut allocator = BuddyAllocator::new();
+    allocator.add_region(0, 8);
+    let a = allocator.alloc(1).unwrap();
+    let b = allocator.alloc(2).unwrap();
+    let c = allocator.alloc(4).unwrap();
+    assert_eq!(allocator.alloc(4), None);
+    allocator.dealloc(b, 2);
+    allocator.dealloc(a, 1);
+    allocator.dealloc(c, 4);
+    // 所有块都合并回一个 8 页的块
+    assert_eq!(allocator.alloc(8), Some(0));
+}
+
+#[test]
+fn multiple_regions() {
+    let mut allocator = BuddyAllocator::new();
+    allocator.add_region(0x100, 0x108);
+    allocator.add_region(0x200, 0x210);
+    assert_eq!(allocator.total_frames(), 0x18);
+    assert_eq!(allocator.alloc(16), Some(0x200));
+    assert_eq!(allocator.alloc(8), Some(0x100));
+    assert_eq!(allocator.alloc(1), None);
+}
+
+#[test]
+#[should_panic]
+fn double_free() {
+    let mut allocator = BuddyAllocator::new();
+    allocator.add_region(0, 4);
+    let frame = allocator.alloc(1).unwrap();
+    allocator.dealloc(frame, 1);
+    allocator.dealloc(frame, 1);
+}
+
+#[test]
+fn random_stress() {
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    let mut allocator = BuddyAllocator::new();
+    let regions = [(0x80400, 0x80400 + 3000), (0x90000, 0x90000 + 777), (0xa0001, 0xa0001 + 1024)];
+    let mut total = 0;
+    for &(start, end) in regions.iter() {
+        allocator.add_region(start, end);
+        total += end - start;
+    }
+    // 起始页号到块大小
+    let mut allocated: BTreeMap<usize, usize> = BTreeMap::new();
+    for _ in 0..20000 {
+        if allocated.is_empty() || rng.below(3) != 0 {
+            let max = if rng.below(8) == 0 { 64 } else { 4 };
+            let size = 1 + rng.below(max);
+            if let Some(frame) = allocator.alloc(size) {
+                let len = size.next_power_of_two();
+                assert_eq!(frame % len, 0);
+                assert!(regions.iter().any(|&(start, end)| start <= frame && frame + len <= end));
+                // 与已经分配的块都不重叠
+                if let Some((&prev, &prev_len)) = allocated.range(..frame).next_back() {
+                    assert!(prev + prev_len <= frame);
+                }
+                if let Some((&next, _)) = allocated.range(frame..).next() {
+                    assert!(frame + len <= next);
+                }
+                allocated.insert(frame, len);
+            }
+        } else {
+            let idx = rng.below(allocated.len());
+            let (&frame, &len) = allocated.iter().nth(idx).unwrap();
+            allocated.remove(&frame);
+            allocator.dealloc(frame, len);
+        }
+        let used: usize = allocated.values().sum();
+        assert_eq!(allocator.used_frames(), used);
+        assert_eq!(allocator.free_frames(), total - used);
+    }
+    for (frame, len) in allocated {
+        allocator.dealloc(frame, len);
+    }
+    assert_eq!(allocator.free_frames(), total);
+    // 全部释放后每段都能重新完整地分配出来
+    let mut count = 0;
+    while allocator.alloc(1).is_some() {
+        count += 1;
+    }
+    assert_eq!(count, total);
+}
@@ -312,21 +312,37 @@ impl DeviceTree {
         Ok(dtb)
     }
 
-    pub fn dtb_query_memory(dtb: usize) -> Option<(usize,usize)> {
+    /// Load the flattened device tree at address `dtb`.
+    pub fn from_dtb(dtb: usize) -> Option<DeviceTree> {
         let header = unsafe{ &*(dtb as *const DtbHeader) };
         let magic = u32::from_be(header.magic);
         if magic == 0xd00dfeed {
-            let size = u32::from_be(header.size); 
+            let size = u32::from_be(header.size);
             let dtb_data = unsafe { slice::from_raw_parts(dtb as *const u8, size as usize) };
-
-            if let Ok(data) = DeviceTree::load(dtb_data) {
-                if let Some(ret) = Node::query_memory(&data.root) {
-                    return Some(ret);
-                }
-            }
+            return DeviceTree::load(dtb_data).ok();
         }
         None
     }
+
+    pub fn dtb_query_memory(dtb: usize) -> Option<(usize,usize)> {
+        DeviceTree::from_dtb(dtb).and_then(|data| Node::query_memory(&data.root))
+    }
+
+    /// Query all `(address, size)` pairs in the `reg` of every memory node.
+    pub fn dtb_query_memory_regions(dtb: usize) -> Vec<(usize, usize)> {
+        let mut regions = Vec::new();
+        if let Some(data) = DeviceTree::from_dtb(dtb) {
+            Node::query_memory_regions(&data.root, &mut regions);
+        }
+        regions
+    }
+
+    /// Query the kernel command line in `/chosen/bootargs`.
+    pub fn dtb_query_bootargs(dtb: usize) -> Option<String> {
+        let data = DeviceTree::from_dtb(dtb)?;
+        let chosen = data.find("/chosen")?;
+        chosen.prop_str("bootargs").ok().map(|bootargs| bootargs.to_owned())
+    }
 }
 
 impl Node {
@@ -426,6 +442,30 @@ impl Node {
         }
     }
 
+    /// Check whether one of the strings in `compatible` equals `name`.
+    pub fn is_compatible(&self, name: &str) -> bool {
+        match self.prop_raw("compatible") {
+            Some(raw) => raw.split(|&c| c == 0).any(|s| s == name.as_bytes()),
+            None => false,
+        }
+    }
+
+    /// Visit this node and all its descendants in depth-first order.
+    pub fn walk<F: FnMut(&Node)>(&self, f: &mut F) {
+        f(self);
+        for child in self.children.iter() {
+            child.walk(f);
+        }
+    }
+
+    /// Find the first node (including this one) compatible with `name`.
+    pub fn find_compatible<'a>(&'a self, name: &str) -> Option<&'a Node> {
+        if self.is_compatible(name) {
+            return Some(self);
+        }
+        self.children.iter().filter_map(|child| child.find_compatible(name)).next()
+    }
+
     pub fn has_prop(&self, name: &str) -> bool {
         if let Some(_) = self.prop_raw(name) {
             true
@@ -522,6 +562,25 @@ impl Node {
         }
         None
     }
+
+    fn query_memory_regions(data_root: &Node, regions: &mut Vec<(usize, usize)>) {
+        if let Ok(device_type) = data_root.prop_str("device_type") {
+            if device_type == "memory" {
+                if let Some(reg) = data_root.prop_raw("reg") {
+                    let reg = reg.as_slice();
+                    let mut pos = 0;
+                    while pos + 16 <= reg.len() {
+                        regions.push((reg.read_be_u64(pos).unwrap() as usize,
+                            reg.read_be_u64(pos + 8).unwrap() as usize));
+                        pos += 16;
+                    }
+                }
+            }
+        }
+        for child in data_root.children.iter() {
+            Node::query_memory_regions(child, regions);
+        }
+    }
 }
 
 impl From<str::Utf8Error> for PropError {
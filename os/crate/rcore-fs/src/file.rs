@@ -1,6 +1,7 @@
-use crate::vfs::{INode, Metadata, Result};
+use crate::vfs::{FsError, INode, Metadata, Result};
 use alloc::{string::String, sync::Arc};
 
+#[derive(Clone)]
 pub struct File {
     inode: Arc<INode>,
     offset: usize,
@@ -8,6 +9,14 @@ pub struct File {
     writable: bool,
 }
 
+/// Enumeration of possible methods to seek within a file.
+#[derive(Debug, Clone, Copy)]
+pub enum SeekFrom {
+    Start(u64),
+    End(i64),
+    Current(i64),
+}
+
 impl File {
     pub fn new(inode: Arc<INode>, readable: bool, writable: bool) -> Self {
         File {
@@ -18,20 +27,46 @@ impl File {
         }
     }
 
+    pub fn readable(&self) -> bool {
+        self.readable
+    }
+
+    pub fn writable(&self) -> bool {
+        self.writable
+    }
+
     pub fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-        assert!(self.readable);
+        if !self.readable {
+            return Err(FsError::InvalidParam);
+        }
         let len = self.inode.read_at(self.offset, buf)?;
         self.offset += len;
         Ok(len)
     }
 
     pub fn write(&mut self, buf: &[u8]) -> Result<usize> {
-        assert!(self.writable);
+        if !self.writable {
+            return Err(FsError::InvalidParam);
+        }
         let len = self.inode.write_at(self.offset, buf)?;
         self.offset += len;
         Ok(len)
     }
 
+    /// Move the offset, return the new offset from the start of the file.
+    pub fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let offset = match pos {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::End(offset) => self.inode.metadata()?.size as i64 + offset,
+            SeekFrom::Current(offset) => self.offset as i64 + offset,
+        };
+        if offset < 0 {
+            return Err(FsError::InvalidParam);
+        }
+        self.offset = offset as usize;
+        Ok(self.offset as u64)
+    }
+
     pub fn info(&self) -> Result<Metadata> {
         self.inode.metadata()
     }
@@ -39,4 +74,8 @@ impl File {
     pub fn get_entry(&self, id: usize) -> Result<String> {
         self.inode.get_entry(id)
     }
+
+    pub fn inode(&self) -> Arc<INode> {
+        self.inode.clone()
+    }
 }
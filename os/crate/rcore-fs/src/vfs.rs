@@ -244,6 +244,8 @@ pub enum FsError {
     DirNotEmpty,   //E_NOTEMPTY
     WrongFs,       //E_INVAL, when we find the content on disk is wrong when opening the device
     DeviceError,
+    BrokenPipe,    //E_PIPE, write to a pipe whose read end is closed
+    Interrupted,   //E_INTR, the waiting process was killed
 }
 
 impl fmt::Display for FsError {
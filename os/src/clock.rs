@@ -1,5 +1,12 @@
 pub static mut TICK: usize = 0;
 
+// TIMEBASE 个时钟周期触发一次时钟中断，qemu 中 mtime 的频率为 10MHz
+pub const TICKS_PER_SEC: usize = 100;
+
+pub fn get_tick() -> usize {
+    unsafe { TICK }
+}
+
 use riscv::register::sie;
 pub fn init() {
     unsafe{
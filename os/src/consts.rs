@@ -6,10 +6,24 @@ pub const PAGE_SIZE: usize = 4096;
 
 pub const MAX_DTB_SIZE: usize = 0x2000;
 
-pub const KERNEL_HEAP_SIZE: usize = 0x00a0_0000;
+// 启动时内核堆的大小，不够时再从页帧分配器中增长
+pub const KERNEL_HEAP_SIZE: usize = 0x0010_0000;
+
+// 从 KERNEL_OFFSET 开始的这么大的区域线性映射物理内存，其中的页帧内核可以直接访问
+pub const KERNEL_LINEAR_SIZE: usize = 0x0800_0000;
 
 pub const RECURSIVE_INDEX: usize = 0x3fd;
 
+// 设备寄存器映射到内核地址空间的这段区域
+pub const MMIO_START: usize = 0xF000_0000;
+pub const MMIO_END: usize = 0xFC00_0000;
+
+// 用户堆从程序末尾向上增长到这里为止，mmap 从这里开始分配地址
+pub const USER_MMAP_OFFSET: usize = 0x4000_0000;
+
 pub const USER_STACK_OFFSET: usize = 0x80000000 - USER_STACK_SIZE;
 
 pub const USER_STACK_SIZE: usize = 0x10000;
+
+// 是否允许加载同时可写和可执行的 ELF 段
+pub const ALLOW_WRITABLE_EXEC: bool = false;
@@ -3,6 +3,7 @@ use riscv::register::{
     scause::Scause,
 };
 
+#[derive(Clone)]
 #[repr(C)]
 pub struct TrapFrame {
     pub x: [usize; 32], // General registers
@@ -30,13 +31,23 @@ impl Context {
         ContextContent::new_kernel_thread(entry, arg, kstack_top, satp).push_at(kstack_top)
     }
 
+    // args 为传给用户程序入口的 a0、a1、a2
     pub unsafe fn new_user_thread(
         entry: usize,
         ustack_top : usize,
+        args : [usize; 3],
         kstack_top : usize,
         satp : usize
     ) -> Self {
-        ContextContent::new_user_thread(entry, ustack_top, satp).push_at(kstack_top)
+        ContextContent::new_user_thread(entry, ustack_top, args, satp).push_at(kstack_top)
+    }
+
+    pub unsafe fn new_fork(
+        tf: &TrapFrame,
+        kstack_top: usize,
+        satp: usize
+    ) -> Self {
+        ContextContent::new_fork(tf, satp).push_at(kstack_top)
     }
 
     #[naked]
@@ -72,7 +83,7 @@ impl ContextContent {
         content
     }
 
-    fn new_user_thread(entry : usize, ustack_top : usize, satp : usize) -> Self {
+    fn new_user_thread(entry : usize, ustack_top : usize, args : [usize; 3], satp : usize) -> Self {
         ContextContent{
             ra: __trapret as usize,
             satp,
@@ -80,6 +91,7 @@ impl ContextContent {
             tf: {
                 let mut tf: TrapFrame = unsafe { zeroed() };
                 tf.x[2] = ustack_top;   // 栈顶 sp
+                tf.x[10..13].copy_from_slice(&args);
                 tf.sepc = entry;   // sepc 在调用 sret 之后将被被赋值给 PC
                 tf.sstatus = sstatus::read();
                 tf.sstatus.set_spie(true);
@@ -90,6 +102,19 @@ impl ContextContent {
         }
     }
 
+    fn new_fork(tf: &TrapFrame, satp: usize) -> Self {
+        ContextContent{
+            ra: __trapret as usize,
+            satp,
+            s: [0;12],
+            tf: {
+                let mut tf = tf.clone();
+                tf.x[10] = 0;   // 子进程中 fork 的返回值为 0，sepc 在处理系统调用前已经跳过了 ecall
+                tf
+            },
+        }
+    }
+
     unsafe fn push_at(self, stack_top: usize) -> Context {
         let ptr = (stack_top as *mut ContextContent).sub(1);
         *ptr = self; // 拷贝 ContextContent
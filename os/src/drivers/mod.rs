@@ -0,0 +1,65 @@
+use alloc::{ boxed::Box, collections::BTreeMap, sync::Arc };
+use device_tree::{ DeviceTree, util::SliceRead };
+use crate::memory::ioremap;
+use lazy_static::*;
+use crate::sync::spinlock::IrqSpinLock;
+
+pub mod plic;
+pub mod uart;
+pub mod virtio_blk;
+
+pub type IrqHandler = Arc<Fn() + Send + Sync>;
+
+lazy_static! {
+    // 外部中断号到处理函数的映射
+    static ref IRQ_HANDLERS: IrqSpinLock<BTreeMap<u32, IrqHandler>> = IrqSpinLock::new(BTreeMap::new());
+}
+
+// 根据设备树初始化设备，找不到的设备保持未初始化，使用 SBI 提供的功能代替
+pub fn init(dtb: usize) {
+    let dt = match DeviceTree::from_dtb(dtb) {
+        Some(dt) => dt,
+        None => {
+            println!("failed to parse device tree");
+            return;
+        },
+    };
+    plic::init(&dt.root);
+    uart::init(&dt.root);
+    dt.root.walk(&mut |node| {
+        if node.is_compatible("virtio,mmio") {
+            if let Some(reg) = node.prop_raw("reg") {
+                let paddr = reg.as_slice().read_be_u64(0).unwrap() as usize;
+                let irq = node.prop_raw("interrupts").map(|irq| irq.as_slice().read_be_u32(0).unwrap());
+                virtio_blk::probe(ioremap(paddr, 0x1000), irq);
+            }
+        }
+    });
+}
+
+// 注册 irq 号外部中断的处理函数并在 PLIC 中使能该中断，没有 PLIC 时返回 false
+pub fn register_irq(irq: u32, handler: Box<Fn() + Send + Sync>) -> bool {
+    if !plic::present() {
+        return false;
+    }
+    IRQ_HANDLERS.lock().insert(irq, Arc::from(handler));
+    plic::enable(irq);
+    true
+}
+
+// 处理外部中断，返回是否由驱动处理了该中断
+pub fn handle_external() -> bool {
+    if !plic::present() {
+        return false;
+    }
+    while let Some(irq) = plic::claim() {
+        // 先释放锁再调用处理函数，处理函数中可以注册新的中断
+        let handler = IRQ_HANDLERS.lock().get(&irq).cloned();
+        match handler {
+            Some(handler) => handler(),
+            None => println!("unhandled external interrupt {}", irq),
+        }
+        plic::complete(irq);
+    }
+    true
+}
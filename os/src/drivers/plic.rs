@@ -0,0 +1,94 @@
+use core::ptr::{ read_volatile, write_volatile };
+use core::sync::atomic::{ AtomicUsize, Ordering };
+use device_tree::{ Node, util::SliceRead };
+use crate::memory::ioremap;
+
+// 平台级中断控制器（PLIC）
+// 每个核的 M 态和 S 态各对应一个上下文，上下文有各自的使能位、阈值和领取寄存器
+const PRIORITY: usize = 0x0;
+const ENABLE: usize = 0x2000;
+const ENABLE_STRIDE: usize = 0x80;
+const CONTEXT: usize = 0x20_0000;
+const CONTEXT_STRIDE: usize = 0x1000;
+const THRESHOLD: usize = 0x0;
+const CLAIM: usize = 0x4;
+
+// interrupts-extended 中表示 S 态外部中断的中断号
+const IRQ_S_EXT: u32 = 9;
+
+static BASE: AtomicUsize = AtomicUsize::new(0);
+static S_CONTEXT: AtomicUsize = AtomicUsize::new(0);  // 0 号核 S 态的上下文编号
+
+fn reg(offset: usize) -> *mut u32 {
+    (BASE.load(Ordering::Relaxed) + offset) as *mut u32
+}
+
+fn context_reg(offset: usize) -> *mut u32 {
+    reg(CONTEXT + S_CONTEXT.load(Ordering::Relaxed) * CONTEXT_STRIDE + offset)
+}
+
+fn enable_reg(irq: u32) -> *mut u32 {
+    reg(ENABLE + S_CONTEXT.load(Ordering::Relaxed) * ENABLE_STRIDE + irq as usize / 32 * 4)
+}
+
+pub fn init(root: &Node) {
+    let node = match root.find_compatible("riscv,plic0") {
+        Some(node) => node,
+        None => {
+            println!("PLIC not found");
+            return;
+        },
+    };
+    let paddr = node.prop_raw("reg").expect("PLIC has no reg").as_slice().read_be_u64(0).unwrap() as usize;
+    // interrupts-extended 由 <phandle, 中断号> 对组成，第 i 对描述第 i 个上下文
+    // 第一个 S 态外部中断所在的上下文属于 0 号核
+    let context = node.prop_raw("interrupts-extended")
+        .and_then(|prop| {
+            prop.chunks(8)
+                .position(|pair| pair.len() == 8 && (&pair[4..]).read_be_u32(0).unwrap() == IRQ_S_EXT)
+        })
+        .unwrap_or(1);
+    S_CONTEXT.store(context, Ordering::Relaxed);
+    BASE.store(ioremap(paddr, CONTEXT + (context + 1) * CONTEXT_STRIDE), Ordering::Relaxed);
+    // 阈值为 0，优先级大于 0 的中断都会被送达
+    unsafe { write_volatile(context_reg(THRESHOLD), 0); }
+    println!("PLIC @ {:#x}, context {}", paddr, context);
+}
+
+pub fn present() -> bool {
+    BASE.load(Ordering::Relaxed) != 0
+}
+
+// 设置中断的优先级，优先级为 0 的中断不会被送达
+pub fn set_priority(irq: u32, priority: u32) {
+    unsafe { write_volatile(reg(PRIORITY + irq as usize * 4), priority); }
+}
+
+// 在 0 号核的 S 态使能 irq 号中断
+pub fn enable(irq: u32) {
+    set_priority(irq, 1);
+    unsafe {
+        let enable = enable_reg(irq);
+        write_volatile(enable, read_volatile(enable) | 1 << (irq % 32));
+    }
+}
+
+pub fn disable(irq: u32) {
+    unsafe {
+        let enable = enable_reg(irq);
+        write_volatile(enable, read_volatile(enable) & !(1 << (irq % 32)));
+    }
+}
+
+// 领取一个待处理的中断，没有时返回 None
+pub fn claim() -> Option<u32> {
+    match unsafe { read_volatile(context_reg(CLAIM)) } {
+        0 => None,
+        irq => Some(irq),
+    }
+}
+
+// 通知 PLIC 中断处理完成
+pub fn complete(irq: u32) {
+    unsafe { write_volatile(context_reg(CLAIM), irq); }
+}
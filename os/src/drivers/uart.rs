@@ -0,0 +1,178 @@
+use core::ptr::{ read_volatile, write_volatile };
+use core::sync::atomic::{ AtomicBool, AtomicUsize, Ordering };
+use device_tree::{ Node, util::SliceRead };
+use crate::memory::ioremap;
+use crate::sync::spinlock::IrqSpinLock;
+use alloc::boxed::Box;
+use super::register_irq;
+
+// NS16550A 串口，寄存器间隔为 1 字节
+const RBR: usize = 0;   // 接收缓冲（读）
+const THR: usize = 0;   // 发送保持（写）
+const IER: usize = 1;   // 中断使能
+const FCR: usize = 2;   // FIFO 控制（写）
+const LCR: usize = 3;   // 线路控制
+const MCR: usize = 4;   // modem 控制
+const LSR: usize = 5;   // 线路状态
+
+const IER_RX: u8 = 1 << 0;     // 接收到数据时产生中断
+const IER_THRE: u8 = 1 << 1;   // 发送保持寄存器为空时产生中断
+
+const LSR_DATA_READY: u8 = 1 << 0;
+const LSR_THR_EMPTY: u8 = 1 << 5;
+
+const FIFO_SIZE: usize = 16;
+const TX_BUF_SIZE: usize = 0x1000;
+
+static BASE: AtomicUsize = AtomicUsize::new(0);
+// 串口中断已经注册，发送缓冲区可以由发送中断清空
+static IRQ_ENABLED: AtomicBool = AtomicBool::new(false);
+
+// 等待发送的字符组成的环形缓冲区
+struct TxRing {
+    buf: [u8; TX_BUF_SIZE],
+    head: usize,
+    len: usize,
+}
+
+impl TxRing {
+    fn push(&mut self, ch: u8) -> bool {
+        if self.len == TX_BUF_SIZE {
+            return false;
+        }
+        self.buf[(self.head + self.len) % TX_BUF_SIZE] = ch;
+        self.len += 1;
+        true
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        let ch = self.buf[self.head];
+        self.head = (self.head + 1) % TX_BUF_SIZE;
+        self.len -= 1;
+        Some(ch)
+    }
+}
+
+static TX: IrqSpinLock<TxRing> = IrqSpinLock::new(TxRing { buf: [0; TX_BUF_SIZE], head: 0, len: 0 });
+
+#[derive(Clone, Copy)]
+pub struct Uart {
+    base: usize,
+}
+
+impl Uart {
+    fn read(&self, reg: usize) -> u8 {
+        unsafe { read_volatile((self.base + reg) as *const u8) }
+    }
+
+    fn write_reg(&self, reg: usize, value: u8) {
+        unsafe { write_volatile((self.base + reg) as *mut u8, value); }
+    }
+
+    fn init(&self) {
+        self.write_reg(IER, 0);
+        self.write_reg(LCR, 0x03);  // 8 位数据，无校验，1 位停止位
+        self.write_reg(FCR, 0x07);  // 使能并清空收发 FIFO
+        self.write_reg(MCR, 0x08);  // OUT2，允许串口向外发出中断
+        self.write_reg(IER, IER_RX);
+    }
+
+    pub fn getchar(&self) -> Option<u8> {
+        if self.read(LSR) & LSR_DATA_READY != 0 {
+            Some(self.read(RBR))
+        } else {
+            None
+        }
+    }
+
+    // 没有串口中断时直接写串口，发送 FIFO 为空时一次最多可以写入 FIFO_SIZE 个字节
+    // 否则放入发送缓冲区，由发送中断写入串口。缓冲区满时（例如长时间关中断）轮询串口把缓冲区腾出空间
+    pub fn write(&self, buf: &[u8]) {
+        if !IRQ_ENABLED.load(Ordering::Relaxed) {
+            for chunk in buf.chunks(FIFO_SIZE) {
+                while self.read(LSR) & LSR_THR_EMPTY == 0 {}
+                for &ch in chunk {
+                    self.write_reg(THR, ch);
+                }
+            }
+            return;
+        }
+        let mut tx = TX.lock();
+        for &ch in buf {
+            while !tx.push(ch) {
+                while self.read(LSR) & LSR_THR_EMPTY == 0 {}
+                self.fill_fifo(&mut tx);
+            }
+        }
+        self.fill_fifo(&mut tx);
+    }
+
+    // 发送 FIFO 为空时从缓冲区取出字符写入。缓冲区中还有字符时打开发送中断，否则关闭
+    fn fill_fifo(&self, tx: &mut TxRing) {
+        if self.read(LSR) & LSR_THR_EMPTY != 0 {
+            for _ in 0..FIFO_SIZE {
+                match tx.pop() {
+                    Some(ch) => self.write_reg(THR, ch),
+                    None => break,
+                }
+            }
+        }
+        let ier = if tx.len > 0 { IER_RX | IER_THRE } else { IER_RX };
+        self.write_reg(IER, ier);
+    }
+}
+
+pub fn init(root: &Node) {
+    let node = match root.find_compatible("ns16550a") {
+        Some(node) => node,
+        None => {
+            println!("UART not found, use SBI console");
+            return;
+        },
+    };
+    let paddr = node.prop_raw("reg").expect("UART has no reg").as_slice().read_be_u64(0).unwrap() as usize;
+    let uart = Uart { base: ioremap(paddr, 8) };
+    uart.init();
+    BASE.store(uart.base, Ordering::Relaxed);
+    // 没有中断控制器时收不到串口中断，仍然通过 SBI 读取输入，输出也不经过缓冲区
+    if let Some(irq) = node.prop_raw("interrupts") {
+        if register_irq(irq.as_slice().read_be_u32(0).unwrap(), Box::new(handle_interrupt)) {
+            IRQ_ENABLED.store(true, Ordering::Relaxed);
+        }
+    }
+    println!("UART @ {:#x}", paddr);
+}
+
+pub fn uart() -> Option<Uart> {
+    match BASE.load(Ordering::Relaxed) {
+        0 => None,
+        base => Some(Uart { base }),
+    }
+}
+
+// 把收到的字符全部放入标准输入，并继续发送缓冲区中的字符
+pub fn handle_interrupt() {
+    if let Some(uart) = uart() {
+        while let Some(ch) = uart.getchar() {
+            crate::fs::stdio::STDIN.push(ch as char);
+        }
+        uart.fill_fifo(&mut TX.lock());
+    }
+}
+
+// 轮询串口，发送完缓冲区中的所有字符。用于 panic 时，缓冲区被占用时放弃
+pub fn flush() {
+    let uart = match uart() {
+        Some(uart) => uart,
+        None => return,
+    };
+    if let Some(mut tx) = TX.try_lock() {
+        while tx.len > 0 {
+            while uart.read(LSR) & LSR_THR_EMPTY == 0 {}
+            uart.fill_fifo(&mut tx);
+        }
+    }
+}
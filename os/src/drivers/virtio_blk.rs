@@ -0,0 +1,336 @@
+use alloc::alloc::{ alloc_zeroed, Layout };
+use alloc::{ boxed::Box, vec::Vec };
+use core::mem::size_of;
+use core::ptr::{ read_volatile, write_volatile };
+use core::sync::atomic::{ fence, Ordering };
+use lazy_static::*;
+use rcore_fs::dev::{ BlockDevice, BlockId, DevError, Result };
+use spin::Mutex;
+use crate::consts::PAGE_SIZE;
+use crate::memory::kernel_virt_to_phys;
+use super::register_irq;
+
+// virtio-mmio 块设备，同时支持 legacy（版本 1）和 modern（版本 2）接口
+// 同一时刻只有一个请求，提交后轮询等待设备完成
+
+const MAGIC_VALUE: usize = 0x000;
+const VERSION: usize = 0x004;
+const DEVICE_ID: usize = 0x008;
+const DEVICE_FEATURES_SEL: usize = 0x014;
+const DRIVER_FEATURES: usize = 0x020;
+const DRIVER_FEATURES_SEL: usize = 0x024;
+const GUEST_PAGE_SIZE: usize = 0x028;   // legacy
+const QUEUE_SEL: usize = 0x030;
+const QUEUE_NUM_MAX: usize = 0x034;
+const QUEUE_NUM: usize = 0x038;
+const QUEUE_ALIGN: usize = 0x03c;       // legacy
+const QUEUE_PFN: usize = 0x040;         // legacy
+const QUEUE_READY: usize = 0x044;       // modern
+const QUEUE_NOTIFY: usize = 0x050;
+const INTERRUPT_STATUS: usize = 0x060;
+const INTERRUPT_ACK: usize = 0x064;
+const STATUS: usize = 0x070;
+const QUEUE_DESC_LOW: usize = 0x080;    // modern
+const QUEUE_DESC_HIGH: usize = 0x084;
+const QUEUE_AVAIL_LOW: usize = 0x090;
+const QUEUE_AVAIL_HIGH: usize = 0x094;
+const QUEUE_USED_LOW: usize = 0x0a0;
+const QUEUE_USED_HIGH: usize = 0x0a4;
+const CONFIG: usize = 0x100;
+
+const MAGIC: u32 = 0x7472_6976;     // "virt"
+const DEVICE_BLK: u32 = 2;
+
+const STATUS_ACKNOWLEDGE: u32 = 1;
+const STATUS_DRIVER: u32 = 2;
+const STATUS_DRIVER_OK: u32 = 4;
+const STATUS_FEATURES_OK: u32 = 8;
+
+const VIRTIO_F_VERSION_1: u32 = 1 << 0;    // 特性的第 32 位，在第二组特性中
+
+const DESC_F_NEXT: u16 = 1;
+const DESC_F_WRITE: u16 = 2;
+
+const BLK_T_IN: u32 = 0;
+const BLK_T_OUT: u32 = 1;
+
+const QUEUE_SIZE: usize = 8;
+const SECTOR_SIZE: usize = 512;
+
+#[repr(C)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+#[repr(C)]
+struct AvailRing {
+    flags: u16,
+    idx: u16,
+    ring: [u16; QUEUE_SIZE],
+    used_event: u16,
+}
+
+#[repr(C)]
+struct UsedElem {
+    id: u32,
+    len: u32,
+}
+
+#[repr(C)]
+struct UsedRing {
+    flags: u16,
+    idx: u16,
+    ring: [UsedElem; QUEUE_SIZE],
+    avail_event: u16,
+}
+
+#[repr(C)]
+struct BlkReqHeader {
+    type_: u32,
+    reserved: u32,
+    sector: u64,
+}
+
+// 请求头、状态和数据都放在一页中，设备通过物理地址访问
+// 用户传入的缓冲区不一定在线性映射的内核内存中，因此先拷贝到这里
+#[repr(C)]
+struct BlkReq {
+    header: BlkReqHeader,
+    status: u8,
+    data: [u8; SECTOR_SIZE],
+}
+
+struct VirtIOBlkInner {
+    base: usize,
+    desc: *mut [Descriptor; QUEUE_SIZE],
+    avail: *mut AvailRing,
+    used: *mut UsedRing,
+    req: *mut BlkReq,
+    last_used: u16,
+}
+
+unsafe impl Send for VirtIOBlkInner {}
+
+pub struct VirtIOBlk {
+    inner: Mutex<VirtIOBlkInner>,
+    capacity: usize,    // 扇区数
+}
+
+lazy_static! {
+    // 探测到的、还没有被取走的块设备
+    static ref DEVICES: Mutex<Vec<VirtIOBlk>> = Mutex::new(Vec::new());
+}
+
+// 从内核堆中分配按页对齐、物理上连续的内存，内核堆在线性映射的区域中
+fn alloc_dma(pages: usize) -> usize {
+    unsafe { alloc_zeroed(Layout::from_size_align(pages * PAGE_SIZE, PAGE_SIZE).unwrap()) as usize }
+}
+
+impl VirtIOBlkInner {
+    fn read(&self, reg: usize) -> u32 {
+        unsafe { read_volatile((self.base + reg) as *const u32) }
+    }
+
+    fn write(&self, reg: usize, value: u32) {
+        unsafe { write_volatile((self.base + reg) as *mut u32, value); }
+    }
+
+    fn init(&mut self) -> bool {
+        let version = self.read(VERSION);
+        self.write(STATUS, 0);
+        self.write(STATUS, STATUS_ACKNOWLEDGE);
+        self.write(STATUS, STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+        // 不使用任何可选特性，modern 设备必须接受 VIRTIO_F_VERSION_1
+        self.write(DRIVER_FEATURES_SEL, 0);
+        self.write(DRIVER_FEATURES, 0);
+        if version == 2 {
+            self.write(DEVICE_FEATURES_SEL, 1);
+            self.write(DRIVER_FEATURES_SEL, 1);
+            self.write(DRIVER_FEATURES, VIRTIO_F_VERSION_1);
+            self.write(STATUS, STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_FEATURES_OK);
+            if self.read(STATUS) & STATUS_FEATURES_OK == 0 {
+                return false;
+            }
+        } else {
+            self.write(GUEST_PAGE_SIZE, PAGE_SIZE as u32);
+        }
+
+        self.write(QUEUE_SEL, 0);
+        if (self.read(QUEUE_NUM_MAX) as usize) < QUEUE_SIZE {
+            return false;
+        }
+        self.write(QUEUE_NUM, QUEUE_SIZE as u32);
+        // legacy 接口要求可用环紧跟在描述符表之后，已用环从下一页开始
+        let queue = alloc_dma(2);
+        self.desc = queue as *mut _;
+        self.avail = (queue + size_of::<[Descriptor; QUEUE_SIZE]>()) as *mut _;
+        self.used = (queue + PAGE_SIZE) as *mut _;
+        self.req = alloc_dma(1) as *mut _;
+        let (desc, avail, used) = (
+            kernel_virt_to_phys(self.desc as usize) as u32,
+            kernel_virt_to_phys(self.avail as usize) as u32,
+            kernel_virt_to_phys(self.used as usize) as u32,
+        );
+        if version == 2 {
+            self.write(QUEUE_DESC_LOW, desc);
+            self.write(QUEUE_DESC_HIGH, 0);
+            self.write(QUEUE_AVAIL_LOW, avail);
+            self.write(QUEUE_AVAIL_HIGH, 0);
+            self.write(QUEUE_USED_LOW, used);
+            self.write(QUEUE_USED_HIGH, 0);
+            self.write(QUEUE_READY, 1);
+            self.write(STATUS, STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_FEATURES_OK | STATUS_DRIVER_OK);
+        } else {
+            self.write(QUEUE_ALIGN, PAGE_SIZE as u32);
+            self.write(QUEUE_PFN, desc / PAGE_SIZE as u32);
+            self.write(STATUS, STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_DRIVER_OK);
+        }
+        true
+    }
+
+    // 提交一个读写请求并等待完成，数据在 req.data 中
+    fn request(&mut self, sector: usize, write: bool) -> Result<()> {
+        unsafe {
+            let req = &mut *self.req;
+            req.header = BlkReqHeader {
+                type_: if write { BLK_T_OUT } else { BLK_T_IN },
+                reserved: 0,
+                sector: sector as u64,
+            };
+            req.status = 0xff;
+            let base = kernel_virt_to_phys(self.req as usize) as u64;
+            let desc = &mut *self.desc;
+            desc[0] = Descriptor {
+                addr: base,
+                len: size_of::<BlkReqHeader>() as u32,
+                flags: DESC_F_NEXT,
+                next: 1,
+            };
+            desc[1] = Descriptor {
+                addr: base + offset_of_data() as u64,
+                len: SECTOR_SIZE as u32,
+                flags: DESC_F_NEXT | if write { 0 } else { DESC_F_WRITE },
+                next: 2,
+            };
+            desc[2] = Descriptor {
+                addr: base + offset_of_status() as u64,
+                len: 1,
+                flags: DESC_F_WRITE,
+                next: 0,
+            };
+            let avail = &mut *self.avail;
+            let idx = read_volatile(&avail.idx);
+            write_volatile(&mut avail.ring[idx as usize % QUEUE_SIZE], 0);
+            fence(Ordering::SeqCst);
+            write_volatile(&mut avail.idx, idx.wrapping_add(1));
+            fence(Ordering::SeqCst);
+            self.write(QUEUE_NOTIFY, 0);
+
+            while read_volatile(&(*self.used).idx) == self.last_used {}
+            fence(Ordering::SeqCst);
+            self.last_used = self.last_used.wrapping_add(1);
+            self.ack_interrupt();
+            match read_volatile(&req.status) {
+                0 => Ok(()),
+                _ => Err(DevError),
+            }
+        }
+    }
+
+    fn ack_interrupt(&self) {
+        ack_interrupt(self.base);
+    }
+}
+
+fn ack_interrupt(base: usize) {
+    unsafe {
+        let status = read_volatile((base + INTERRUPT_STATUS) as *const u32);
+        write_volatile((base + INTERRUPT_ACK) as *mut u32, status);
+    }
+}
+
+fn offset_of_status() -> usize {
+    size_of::<BlkReqHeader>()
+}
+
+fn offset_of_data() -> usize {
+    size_of::<BlkReqHeader>() + 1
+}
+
+impl BlockDevice for VirtIOBlk {
+    const BLOCK_SIZE_LOG2: u8 = 9;
+
+    fn read_at(&self, block_id: BlockId, buf: &mut [u8]) -> Result<()> {
+        if block_id >= self.capacity {
+            return Err(DevError);
+        }
+        let mut inner = self.inner.lock();
+        inner.request(block_id, false)?;
+        buf[..SECTOR_SIZE].copy_from_slice(unsafe { &(*inner.req).data });
+        Ok(())
+    }
+
+    fn write_at(&self, block_id: BlockId, buf: &[u8]) -> Result<()> {
+        if block_id >= self.capacity {
+            return Err(DevError);
+        }
+        let mut inner = self.inner.lock();
+        unsafe { (*inner.req).data.copy_from_slice(&buf[..SECTOR_SIZE]); }
+        inner.request(block_id, true)
+    }
+
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl VirtIOBlk {
+    // 容量，单位为字节
+    pub fn capacity(&self) -> usize {
+        self.capacity * SECTOR_SIZE
+    }
+}
+
+// 检查 base 处的 virtio-mmio 设备，是块设备时初始化并保存下来
+pub fn probe(base: usize, irq: Option<u32>) {
+    let mut inner = VirtIOBlkInner {
+        base,
+        desc: 0 as *mut _,
+        avail: 0 as *mut _,
+        used: 0 as *mut _,
+        req: 0 as *mut _,
+        last_used: 0,
+    };
+    if inner.read(MAGIC_VALUE) != MAGIC || inner.read(DEVICE_ID) != DEVICE_BLK {
+        return;
+    }
+    if !inner.init() {
+        println!("failed to initialize virtio-blk");
+        return;
+    }
+    let capacity = (inner.read(CONFIG) as u64 | (inner.read(CONFIG + 4) as u64) << 32) as usize;
+    println!("virtio-blk: {} sectors", capacity);
+    DEVICES.lock().push(VirtIOBlk {
+        inner: Mutex::new(inner),
+        capacity,
+    });
+    // 请求是轮询完成的，中断只需要应答
+    if let Some(irq) = irq {
+        register_irq(irq, Box::new(move || ack_interrupt(base)));
+    }
+}
+
+// 取出一个探测到的块设备
+pub fn take() -> Option<VirtIOBlk> {
+    take_if(|_| true)
+}
+
+// 取出第一个满足 pred 的块设备
+pub fn take_if(pred: impl Fn(&VirtIOBlk) -> bool) -> Option<VirtIOBlk> {
+    let mut devices = DEVICES.lock();
+    let idx = devices.iter().position(|blk| pred(blk))?;
+    Some(devices.remove(idx))
+}
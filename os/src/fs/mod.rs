@@ -1,22 +1,71 @@
 use lazy_static::*;
 use rcore_fs::vfs::*;
+use rcore_fs::dev::{ Device, BlockDevice, block_cache::BlockCache };
 use rcore_fs_sfs::SimpleFileSystem;
 use alloc::{ sync::Arc, vec::Vec };
 
+// 为只支持读写的设备类 INode 实现其余的方法，默认为字符设备
+// 设备不属于任何文件系统，fs 返回根目录所在的文件系统
+macro_rules! impl_inode {
+    () => {
+        impl_inode!(CharDevice);
+    };
+    ($type:ident) => {
+        fn metadata(&self) -> Result<Metadata> {
+            Ok(Metadata {
+                dev: 0,
+                inode: 0,
+                size: 0,
+                blk_size: 0,
+                blocks: 0,
+                atime: Timespec { sec: 0, nsec: 0 },
+                mtime: Timespec { sec: 0, nsec: 0 },
+                ctime: Timespec { sec: 0, nsec: 0 },
+                type_: FileType::$type,
+                mode: 0o666,
+                nlinks: 1,
+                uid: 0,
+                gid: 0,
+            })
+        }
+        fn set_metadata(&self, _metadata: &Metadata) -> Result<()> { Ok(()) }
+        fn sync_all(&self) -> Result<()> { Ok(()) }
+        fn sync_data(&self) -> Result<()> { Ok(()) }
+        fn resize(&self, _len: usize) -> Result<()> { Err(FsError::NotSupported) }
+        fn create(&self, _name: &str, _type: FileType, _mode: u32) -> Result<Arc<INode>> { Err(FsError::NotDir) }
+        fn link(&self, _name: &str, _other: &Arc<INode>) -> Result<()> { Err(FsError::NotDir) }
+        fn unlink(&self, _name: &str) -> Result<()> { Err(FsError::NotDir) }
+        fn move_(&self, _old_name: &str, _target: &Arc<INode>, _new_name: &str) -> Result<()> { Err(FsError::NotDir) }
+        fn find(&self, _name: &str) -> Result<Arc<INode>> { Err(FsError::NotDir) }
+        fn get_entry(&self, _id: usize) -> Result<String> { Err(FsError::NotDir) }
+        fn io_control(&self, _cmd: u32, _data: usize) -> Result<()> { Err(FsError::NotSupported) }
+        fn fs(&self) -> Arc<FileSystem> { crate::fs::ROOT_INODE.fs() }
+        fn as_any_ref(&self) -> &Any { self }
+    };
+}
+
 mod device;
 pub mod stdio;
+pub mod pipe;
 
 lazy_static! {
     /// The root of file system
     pub static ref ROOT_INODE: Arc<INode> = {
 
-        let device = {
-            extern {
-                fn _user_img_start();
-                fn _user_img_end();
-            }
-            // 将存储磁盘文件的内存范围初始化为虚拟磁盘 Membuf
-            Arc::new(unsafe { device::MemBuf::new(_user_img_start, _user_img_end) })
+        let device: Arc<Device> = match crate::drivers::virtio_blk::take_if(|blk| is_sfs(blk)) {
+            // 有 virtio 磁盘时从磁盘挂载，写入的数据会保存下来。其它的磁盘可以用作交换区
+            Some(blk) => {
+                println!("mount SFS from virtio-blk");
+                Arc::new(BlockCache::new(blk, 0x100))
+            },
+            None => {
+                extern {
+                    fn _user_img_start();
+                    fn _user_img_end();
+                }
+                // 将存储磁盘文件的内存范围初始化为虚拟磁盘 Membuf
+                Arc::new(unsafe { device::MemBuf::new(_user_img_start, _user_img_end) })
+            },
         };
 
         let sfs = SimpleFileSystem::open(device).expect("failed to open SFS");
@@ -24,6 +73,18 @@ lazy_static! {
     };
 }
 
+// SFS 超级块开头的魔数，与 rcore-fs-sfs 中的定义相同
+const SFS_MAGIC: u32 = 0x2f8dbe2b;
+
+// 块设备的第一个扇区是否为 SFS 的超级块
+fn is_sfs(blk: &crate::drivers::virtio_blk::VirtIOBlk) -> bool {
+    let mut buf = [0u8; 512];
+    if BlockDevice::read_at(blk, 0, &mut buf).is_err() {
+        return false;
+    }
+    u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) == SFS_MAGIC
+}
+
 pub trait INodeExt {
     fn read_as_vec(&self) -> Result<Vec<u8>>;
 }
@@ -0,0 +1,152 @@
+use alloc::{ collections::VecDeque, string::String, sync::Arc };
+use core::any::Any;
+use spin::Mutex;
+use rcore_fs::vfs::*;
+use crate::sync::condvar::*;
+use crate::interrupt::{ disable_and_store, restore };
+use crate::process;
+
+// 管道缓冲区的大小
+const PIPE_SIZE: usize = 0x1000;
+
+struct PipeInner {
+    buf: VecDeque<u8>,
+    read_closed: bool,  // 读端是否已经全部关闭
+    write_closed: bool, // 写端是否已经全部关闭
+}
+
+// 管道的两端共享的部分
+struct Pipe {
+    inner: Mutex<PipeInner>,
+    readable: Condvar,  // 有数据可读或写端关闭
+    writable: Condvar,  // 有空间可写或读端关闭
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum End {
+    Read,
+    Write,
+}
+
+// 管道的一端。同一端的所有文件描述符共享这一个对象，被释放时表示这一端已经全部关闭
+pub struct PipeEnd {
+    pipe: Arc<Pipe>,
+    end: End,
+}
+
+// 创建一个管道，返回读端和写端
+pub fn make_pipe() -> (Arc<PipeEnd>, Arc<PipeEnd>) {
+    let pipe = Arc::new(Pipe {
+        inner: Mutex::new(PipeInner {
+            buf: VecDeque::with_capacity(PIPE_SIZE),
+            read_closed: false,
+            write_closed: false,
+        }),
+        readable: Condvar::new(),
+        writable: Condvar::new(),
+    });
+    let read = Arc::new(PipeEnd { pipe: pipe.clone(), end: End::Read });
+    let write = Arc::new(PipeEnd { pipe, end: End::Write });
+    (read, write)
+}
+
+impl Drop for PipeEnd {
+    fn drop(&mut self) {
+        let mut inner = self.pipe.inner.lock();
+        match self.end {
+            End::Read => inner.read_closed = true,
+            End::Write => inner.write_closed = true,
+        }
+        drop(inner);
+        // 唤醒另一端所有等待的线程，让它们看到 EOF 或 EPIPE
+        self.pipe.readable.notify_all();
+        self.pipe.writable.notify_all();
+    }
+}
+
+impl INode for PipeEnd {
+    // 缓冲区为空时阻塞，写端全部关闭后返回 0 表示 EOF。等待期间进程被结束时返回 Interrupted
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> Result<usize> {
+        if self.end != End::Read {
+            return Err(FsError::NotSupported);
+        }
+        if buf.len() == 0 {
+            return Ok(0);
+        }
+        loop {
+            // 关中断，避免在发现缓冲区为空之后、进入等待队列之前写端的通知丢失
+            let flags = disable_and_store();
+            let mut inner = self.pipe.inner.lock();
+            if inner.buf.len() > 0 {
+                let len = buf.len().min(inner.buf.len());
+                for (i, byte) in inner.buf.drain(..len).enumerate() {
+                    buf[i] = byte;
+                }
+                drop(inner);
+                restore(flags);
+                self.pipe.writable.notify_all();
+                return Ok(len);
+            }
+            if inner.write_closed {
+                drop(inner);
+                restore(flags);
+                return Ok(0);
+            }
+            if process::killed() {
+                drop(inner);
+                restore(flags);
+                return Err(FsError::Interrupted);
+            }
+            drop(inner);
+            self.pipe.readable.wait();
+            restore(flags);
+        }
+    }
+
+    // 缓冲区满时阻塞直到全部写入。读端全部关闭时返回已写入的长度，一个字节都没有写入则返回 BrokenPipe
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> Result<usize> {
+        if self.end != End::Write {
+            return Err(FsError::NotSupported);
+        }
+        let mut written = 0;
+        while written < buf.len() {
+            // 与 read_at 相同，从发现缓冲区已满到进入等待队列之间不能被中断
+            let flags = disable_and_store();
+            let mut inner = self.pipe.inner.lock();
+            if inner.read_closed {
+                drop(inner);
+                restore(flags);
+                return if written > 0 { Ok(written) } else { Err(FsError::BrokenPipe) };
+            }
+            if process::killed() {
+                drop(inner);
+                restore(flags);
+                return if written > 0 { Ok(written) } else { Err(FsError::Interrupted) };
+            }
+            let len = (buf.len() - written).min(PIPE_SIZE - inner.buf.len());
+            if len > 0 {
+                inner.buf.extend(buf[written..written + len].iter());
+                written += len;
+                drop(inner);
+                restore(flags);
+                self.pipe.readable.notify_all();
+            } else {
+                drop(inner);
+                self.pipe.writable.wait();
+                restore(flags);
+            }
+        }
+        Ok(written)
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        let inner = self.pipe.inner.lock();
+        Ok(PollStatus {
+            read: self.end == End::Read && (inner.buf.len() > 0 || inner.write_closed),
+            write: self.end == End::Write && (inner.buf.len() < PIPE_SIZE || inner.read_closed),
+            error: false,
+        })
+    }
+
+    impl_inode!(NamedPipe);
+}
@@ -1,17 +1,20 @@
-use alloc::{ collections::VecDeque, sync::Arc };
-use spin::Mutex;
-use crate::process;
+use alloc::{ collections::VecDeque, string::String, sync::Arc };
+use core::any::Any;
+use crate::sync::spinlock::IrqSpinLock;
+use crate::interrupt::{ disable_and_store, restore };
+use rcore_fs::vfs::*;
 use crate::sync::condvar::*;
+use crate::process;
 
 pub struct Stdin {
-    buf: Mutex<VecDeque<char>>,
+    buf: IrqSpinLock<VecDeque<char>>,  // push 在外部中断处理函数中调用
     pushed: Condvar,
 }
 
 impl Stdin {
     pub fn new() -> Self {
         Stdin {
-            buf: Mutex::new(VecDeque::new()),
+            buf: IrqSpinLock::new(VecDeque::new()),
             pushed: Condvar::new(),
         }
     }
@@ -23,22 +26,94 @@ impl Stdin {
         self.pushed.notify();
     }
 
-    pub fn pop(&self) -> char {
+    // 等待期间所在的进程被结束时返回 None
+    pub fn pop(&self) -> Option<char> {
         loop{
+            // 关中断，避免在发现缓冲区为空之后、进入等待队列之前到来的字符丢失通知
+            let flags = disable_and_store();
             let ret = self.buf.lock().pop_front();
             match ret {
                 Some(ch) => {
-                    return ch;
+                    restore(flags);
+                    return Some(ch);
+                },
+                None if process::killed() => {
+                    restore(flags);
+                    return None;
                 },
                 None => {
                     self.pushed.wait();
+                    restore(flags);
+                },
+            }
+        }
+    }
+
+    pub fn try_pop(&self) -> Option<char> {
+        self.buf.lock().pop_front()
+    }
+}
+
+pub struct Stdout;
+
+impl INode for Stdin {
+    // 至少读到一个字符才返回，之后只读取缓冲区中已有的字符
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> Result<usize> {
+        if buf.len() == 0 {
+            return Ok(0);
+        }
+        buf[0] = self.pop().ok_or(FsError::Interrupted)? as u8;
+        let mut len = 1;
+        while len < buf.len() {
+            match self.try_pop() {
+                Some(ch) => {
+                    buf[len] = ch as u8;
+                    len += 1;
                 },
+                None => break,
             }
         }
+        Ok(len)
     }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> Result<usize> {
+        Err(FsError::NotSupported)
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        Ok(PollStatus {
+            read: self.buf.lock().len() > 0,
+            write: false,
+            error: false,
+        })
+    }
+
+    impl_inode!();
+}
+
+impl INode for Stdout {
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> Result<usize> {
+        Err(FsError::NotSupported)
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> Result<usize> {
+        crate::io::write(buf);
+        Ok(buf.len())
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        Ok(PollStatus {
+            read: false,
+            write: true,
+            error: false,
+        })
+    }
+
+    impl_inode!();
 }
 
 use lazy_static::*;
 lazy_static!{
     pub static ref STDIN: Arc<Stdin> = Arc::new(Stdin::new());
-}
\ No newline at end of file
+    pub static ref STDOUT: Arc<Stdout> = Arc::new(Stdout);
+}
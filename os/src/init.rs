@@ -4,6 +4,8 @@ use crate::memory::init as memory_init;
 use crate::consts::*;
 use crate::process::{ init as process_init, kmain };
 use crate::fs::init as fs_init;
+use crate::drivers::init as drivers_init;
+use crate::memory::swap::init as swap_init;
 
 global_asm!(include_str!("boot/entry.asm"));
 
@@ -12,9 +14,14 @@ pub extern "C" fn rust_main(hartid: usize, dtb: usize) -> ! {
     interrupt_init();
     println!("Hello RISCV ! in hartid {}, dtb @ {:#x} ", hartid, dtb);
     memory_init(dtb);
+    drivers_init(dtb);
+    // 启动参数来自设备树的 /chosen/bootargs，用 qemu 的 -append 指定
+    let bootargs = device_tree::DeviceTree::dtb_query_bootargs(dtb).unwrap_or_default();
+    println!("bootargs: {}", bootargs);
     fs_init();
+    swap_init(&bootargs);
     clock_init();
-    process_init();
+    process_init(&bootargs);
     kmain();
     loop {}
 }
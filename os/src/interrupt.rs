@@ -20,37 +20,79 @@ impl TrapFrame {
     pub fn increase_sepc(self: &mut Self) {
         self.sepc = self.sepc + 4;
     }
+
+    // 异常是否发生在用户态
+    pub fn from_user(&self) -> bool {
+        match self.sstatus.spp() {
+            sstatus::SPP::User => true,
+            sstatus::SPP::Supervisor => false,
+        }
+    }
 }
 
+// 用户进程因异常被结束时使用的信号编号，退出码为 128 + 信号编号
+pub const SIGILL: usize = 4;
+pub const SIGTRAP: usize = 5;
+pub const SIGBUS: usize = 7;
+pub const SIGSEGV: usize = 11;
+
 use riscv::register::scause::Trap;
 use riscv::register::scause::Exception;
 use riscv::register::scause::Interrupt;
 use crate::clock::{ TICK, clock_set_next_event };
+use crate::memory::{ do_pgfault, PageFault };
 
 #[no_mangle]
 pub extern "C" fn rust_trap(tf: &mut TrapFrame) {
     match tf.scause.cause() {
-        Trap::Exception(Exception::Breakpoint) => breakpoint(),
+        Trap::Exception(Exception::Breakpoint) if !tf.from_user() => breakpoint(),
         Trap::Interrupt(Interrupt::SupervisorTimer) => super_timer(),
         Trap::Exception(Exception::UserEnvCall) => syscall(tf),
-        Trap::Interrupt(Interrupt::SupervisorExternal) => {
-            let ch = bbl::sbi::console_getchar() as u8 as char;
-            external(ch as u8);
+        Trap::Exception(Exception::LoadPageFault) => do_pgfault(tf, PageFault::LoadPageFault),
+        Trap::Exception(Exception::StorePageFault) => do_pgfault(tf, PageFault::StorePageFault),
+        Trap::Exception(Exception::InstructionPageFault) => do_pgfault(tf, PageFault::InstructionPageFault),
+        Trap::Interrupt(Interrupt::SupervisorExternal) => external(),
+        Trap::Exception(exception) if tf.from_user() => {
+            let signal = match exception {
+                Exception::IllegalInstruction => SIGILL,
+                Exception::Breakpoint => SIGTRAP,
+                Exception::InstructionMisaligned | Exception::StoreMisaligned => SIGBUS,
+                _ => SIGSEGV,
+            };
+            user_fault(tf, signal);
         },
-        _ => panic!("unexpected trap"),
+        _ => panic!(
+            "unexpected trap {:?}, scause = {:#x}, stval = {:#x}, sepc = {:#x}",
+            tf.scause.cause(), tf.scause.bits(), tf.stval, tf.sepc
+        ),
     }
+    // 进程被结束后，其余线程不再回到用户态
+    if tf.from_user() {
+        crate::process::exit_if_killed();
+    }
+}
+
+// 用户态的异常无法处理时只结束当前进程的所有线程，不影响内核
+pub fn user_fault(tf: &TrapFrame, signal: usize) {
+    println!(
+        "[pid {}] killed by signal {}: {:?}, pc = {:#x}, addr = {:#x}",
+        crate::process::current_process().map_or(0, |proc| proc.pid),
+        signal, tf.scause.cause(), tf.sepc, tf.stval
+    );
+    crate::process::kill(128 + signal);
 }
 
 pub const SYS_WRITE: usize = 64;
 pub const SYS_EXIT: usize = 93;
 
 fn syscall(tf: &mut TrapFrame) {
+    // 先跳过 ecall，execve 等系统调用可以直接修改 sepc
+    tf.sepc += 4;
     let ret = crate::syscall::syscall(
         tf.x[17],
-        [tf.x[10], tf.x[11], tf.x[12]],
+        [tf.x[10], tf.x[11], tf.x[12], tf.x[13], tf.x[14], tf.x[15]],
         tf,
     );
-    tf.sepc += 4;
     tf.x[10] = ret as usize;
 }
 
@@ -66,6 +108,7 @@ fn super_timer() {
         // if TICK % 100 == 0 {
         //     println!("100 ticks!");
         // }
+        crate::timer::tick(TICK);
     }
     tick();
 }
@@ -93,6 +136,10 @@ pub fn restore(flags: usize) {    // 根据 flag 设置中断
     }
 }
 
-fn external(ch: u8) {
-    crate::fs::stdio::STDIN.push(ch as char);
+fn external() {
+    // 没有中断控制器驱动时，外部中断只可能来自 SBI 转发的串口输入
+    if !crate::drivers::handle_external() {
+        let ch = bbl::sbi::console_getchar() as u8 as char;
+        crate::fs::stdio::STDIN.push(ch);
+    }
 }
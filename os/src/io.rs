@@ -1,13 +1,23 @@
 use bbl::sbi;
 
+// 有串口驱动时交给串口发送，否则通过 SBI 逐字节输出
+pub fn write(buf: &[u8]) {
+    match crate::drivers::uart::uart() {
+        Some(uart) => uart.write(buf),
+        None => {
+            for &ch in buf {
+                sbi::console_putchar(ch as usize);
+            }
+        },
+    }
+}
+
 pub fn putchar(ch: char) {
-    sbi::console_putchar(ch as u8 as usize);
+    write(&[ch as u8]);
 }
 
 pub fn puts(s: &str) {
-    for ch in s.chars() {
-        putchar(ch);
-    }
+    write(s.as_bytes());
 }
 
 #[macro_export]
@@ -4,6 +4,7 @@ use core::panic::PanicInfo;
 #[panic_handler]
 fn panic(_info: &PanicInfo) -> ! {
     println!("{}", _info);
+    crate::drivers::uart::flush();
     loop {}
 }
 
@@ -21,9 +21,11 @@ mod memory_set;
 mod fs;
 mod syscall;
 mod sync;
+mod timer;
+mod drivers;
 
 extern crate alloc;
 
-use buddy_system_allocator::LockedHeap;
+use memory::LockedHeap;
 #[global_allocator]
 static HEAP_ALLOCATOR: LockedHeap = LockedHeap::empty();
\ No newline at end of file
@@ -0,0 +1,22 @@
+# 在内核与用户地址空间之间复制 len 个字节
+#   a0 = dst, a1 = src, a2 = len
+# 成功时返回 0。复制过程中发生无法处理的缺页时，do_pgfault 把 sepc 改为 __copy_user_fault，返回 1
+
+    .section .text
+    .globl __copy_user
+    .globl __copy_user_fault
+__copy_user:
+    beqz a2, 2f
+1:
+    lb t0, 0(a1)
+    sb t0, 0(a0)
+    addi a0, a0, 1
+    addi a1, a1, 1
+    addi a2, a2, -1
+    bnez a2, 1b
+2:
+    li a0, 0
+    ret
+__copy_user_fault:
+    li a0, 1
+    ret
@@ -1,40 +1,53 @@
-use buddy_allocator::{ BuddyAllocator, log2_down };
+use buddy_allocator::BuddyAllocator;
 use lazy_static::*;
-use spin::Mutex;
+use crate::sync::spinlock::IrqSpinLock;
 use riscv::addr::*;
 use crate::consts::*;
+use alloc::collections::BTreeMap;
 
 // 物理页帧分配器
 lazy_static! {
-    pub static ref BUDDY_ALLOCATOR: Mutex<BuddyAllocator>
-        = Mutex::new(BuddyAllocator::new());
+    pub static ref BUDDY_ALLOCATOR: IrqSpinLock<BuddyAllocator>
+        = IrqSpinLock::new(BuddyAllocator::new());
 }
 
-static mut KERNEL_END: usize = 0;
+// 物理页帧的引用计数，只记录被多个页表共享的页帧，不在表中的页帧引用计数为 1
+lazy_static! {
+    static ref FRAME_REF: IrqSpinLock<BTreeMap<usize, usize>>
+        = IrqSpinLock::new(BTreeMap::new());
+}
 
-pub fn init(start: usize, lenth: usize) {
-    unsafe {
-        KERNEL_END = start;
+// regions 为物理内存的各段 (起始地址, 大小)，其中 kernel_end 之前的部分已被内核占用
+// 内核通过线性映射访问页帧的内容（页表、换入换出等），线性映射之外的物理内存不交给分配器
+pub fn init(regions: &[(usize, usize)], kernel_end: usize) {
+    let mut allocator = BUDDY_ALLOCATOR.lock();
+    for &(addr, size) in regions {
+        let start = (addr.max(kernel_end) + PAGE_SIZE - 1) / PAGE_SIZE;
+        let end = (addr + size).min(MEMORY_OFFSET + KERNEL_LINEAR_SIZE) / PAGE_SIZE;
+        if start < end {
+            allocator.add_region(start, end);
+        }
     }
-    let mut bu = BUDDY_ALLOCATOR.lock()
-        .init(log2_down(lenth / PAGE_SIZE) as u8);
-    println!("++++init frame allocator succeed!++++");
+    println!("++++init frame allocator succeed! {} free frames++++", allocator.free_frames());
 }
 
-use riscv::addr::*;
-
+// 没有空闲的页帧时换出一页再试
 pub fn alloc_frame() -> Option<Frame> {
-        alloc_frames(1)
+    loop {
+        if let Some(frame) = alloc_frames(1) {
+            return Some(frame);
+        }
+        if !super::swap::swap_out() {
+            return None;
+        }
+    }
 }
 
 pub fn alloc_frames(size: usize) -> Option<Frame> {
-    unsafe {
-        let ret = BUDDY_ALLOCATOR
-            .lock()
-            .alloc(size)
-            .map(|id| id * PAGE_SIZE + KERNEL_END);
-        ret.map(|addr| Frame::of_addr(PhysAddr::new(addr)))
-    }
+    BUDDY_ALLOCATOR
+        .lock()
+        .alloc(size)
+        .map(|number| Frame::of_addr(PhysAddr::new(number * PAGE_SIZE)))
 }
 
 pub fn dealloc_frame(target: Frame) {
@@ -42,10 +55,43 @@ pub fn dealloc_frame(target: Frame) {
 }
 
 pub fn dealloc_frames(target: Frame, size: usize) {
-    unsafe {
-        BUDDY_ALLOCATOR
-            .lock()
-            .dealloc(target.number() - KERNEL_END / PAGE_SIZE, size);
+    BUDDY_ALLOCATOR
+        .lock()
+        .dealloc(target.number(), size);
+}
+
+// 当前空闲的页帧数
+pub fn free_frames() -> usize {
+    BUDDY_ALLOCATOR.lock().free_frames()
+}
+
+// 已经分配出去的页帧数
+pub fn used_frames() -> usize {
+    BUDDY_ALLOCATOR.lock().used_frames()
+}
+
+pub fn add_frame_ref(target: &Frame) {
+    *FRAME_REF.lock().entry(target.number()).or_insert(1) += 1;
+}
+
+pub fn frame_ref_count(target: &Frame) -> usize {
+    FRAME_REF.lock().get(&target.number()).cloned().unwrap_or(1)
+}
+
+// 引用计数减一，减到 0 时释放该页帧
+pub fn release_frame(target: Frame) {
+    let mut refs = FRAME_REF.lock();
+    match refs.get(&target.number()).cloned().unwrap_or(1) {
+        1 => {
+            drop(refs);
+            dealloc_frame(target);
+        },
+        2 => {
+            refs.remove(&target.number());
+        },
+        count => {
+            refs.insert(target.number(), count - 1);
+        },
     }
 }
 
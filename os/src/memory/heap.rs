@@ -0,0 +1,80 @@
+use buddy_system_allocator::Heap;
+use core::alloc::{ GlobalAlloc, Layout };
+use core::ops::Deref;
+use core::ptr::{ self, NonNull };
+use core::sync::atomic::{ AtomicBool, AtomicUsize, Ordering };
+use crate::consts::PAGE_SIZE;
+use crate::interrupt::{ disable_and_store, restore };
+use crate::sync::spinlock::IrqSpinLock;
+use super::alloc_kernel_pages;
+
+// 内核堆每次至少增长这么多
+const HEAP_GROW_SIZE: usize = 0x4_0000;
+
+// 内核堆分配器。中断处理函数中也会分配内存，因此用关中断的自旋锁保护
+// 空间不足时从页帧分配器中取出一段物理上连续的内存加入堆中
+pub struct LockedHeap {
+    heap: IrqSpinLock<Heap>,
+    growing: AtomicBool,    // 是否正在增长
+    grown: AtomicUsize,     // 增长的总字节数
+}
+
+impl LockedHeap {
+    pub const fn empty() -> Self {
+        LockedHeap {
+            heap: IrqSpinLock::new(Heap::empty()),
+            growing: AtomicBool::new(false),
+            grown: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn grown(&self) -> usize {
+        self.grown.load(Ordering::Relaxed)
+    }
+
+    // 增长到至少可以满足 layout，返回是否成功
+    fn grow(&self, layout: &Layout) -> bool {
+        // 页帧分配器自身也会在堆上分配内存，增长的过程中不能再次增长
+        let flags = disable_and_store();
+        if self.growing.swap(true, Ordering::Relaxed) {
+            restore(flags);
+            return false;
+        }
+        // 页帧分配器分配的块按大小对齐，线性映射后虚拟地址也同样对齐
+        let size = layout.size().max(layout.align()).max(HEAP_GROW_SIZE).next_power_of_two();
+        let ok = match alloc_kernel_pages(size / PAGE_SIZE) {
+            Some(start) => {
+                unsafe { self.heap.lock().add_to_heap(start, start + size); }
+                self.grown.fetch_add(size, Ordering::Relaxed);
+                true
+            },
+            None => false,
+        };
+        self.growing.store(false, Ordering::Relaxed);
+        restore(flags);
+        ok
+    }
+}
+
+impl Deref for LockedHeap {
+    type Target = IrqSpinLock<Heap>;
+    fn deref(&self) -> &IrqSpinLock<Heap> {
+        &self.heap
+    }
+}
+
+unsafe impl GlobalAlloc for LockedHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if let Ok(ptr) = self.heap.lock().alloc(layout) {
+            return ptr.as_ptr();
+        }
+        if !self.grow(&layout) {
+            return ptr::null_mut();
+        }
+        self.heap.lock().alloc(layout).ok().map_or(ptr::null_mut(), |ptr| ptr.as_ptr())
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.heap.lock().dealloc(NonNull::new_unchecked(ptr), layout)
+    }
+}
@@ -1,27 +1,42 @@
 pub mod frame_allocator;
 pub mod paging;
+pub mod slab;
+pub mod swap;
+pub mod shm;
+mod heap;
+
+pub use self::heap::LockedHeap;
 
 use riscv::register::sstatus;
-use frame_allocator::{ init as init_frame_allocator, test as test_frame_allocator };
+use riscv::addr::{ Frame, PhysAddr };
+use core::sync::atomic::{ AtomicBool, Ordering };
+use frame_allocator::{ init as init_frame_allocator, test as test_frame_allocator, alloc_frames, dealloc_frames };
 use crate::consts::*;
 use crate::HEAP_ALLOCATOR;
 
+global_asm!(include_str!("copy_user.asm"));
+
+extern "C" {
+    fn __copy_user(dst: *mut u8, src: *const u8, len: usize) -> usize;
+    fn __copy_user_fault();
+}
+
+// 物理内存的线性映射是否已经建立
+static LINEAR_MAPPED: AtomicBool = AtomicBool::new(false);
+
 pub fn init(dtb: usize) {
-    unsafe {
-        // Allow user memory access
-        sstatus::set_sum();
-    }
 	init_heap();
-    if let Some((addr, mem_size)) = device_tree::DeviceTree::dtb_query_memory(dtb) {
-        assert_eq!(addr, MEMORY_OFFSET);
-        let KERNEL_END = dtb - KERNEL_OFFSET + MEMORY_OFFSET + PAGE_SIZE;
-        let KERNEL_SIZE = KERNEL_END - addr;
-        init_frame_allocator(KERNEL_END, KERNEL_SIZE);
-    } else {
+    let regions = device_tree::DeviceTree::dtb_query_memory_regions(dtb);
+    if regions.is_empty() {
         panic!("failed to query memory");
     }
+    // 设备树紧跟在内核之后，它之后的物理内存都可以分配
+    let kernel_end = dtb - KERNEL_OFFSET + MEMORY_OFFSET + MAX_DTB_SIZE;
+    init_frame_allocator(&regions, kernel_end);
     test_frame_allocator();
-    remap_kernel(dtb);
+    remap_kernel(dtb, &regions, kernel_end);
+    LINEAR_MAPPED.store(true, Ordering::Release);
+    slab::test();
 }
 
 fn init_heap() {
@@ -34,16 +49,109 @@ fn init_heap() {
     println!("heap init end");
 }
 
+// 内核默认不能访问用户页，只在执行 f 期间打开 sstatus.SUM，结束后恢复原来的状态
+pub fn access_user<T>(f: impl FnOnce() -> T) -> T {
+    let sum = sstatus::read().sum();
+    unsafe { sstatus::set_sum(); }
+    let ret = f();
+    if !sum {
+        unsafe { sstatus::clear_sum(); }
+    }
+    ret
+}
+
+// 在内核与用户地址空间之间复制 len 个字节，用户内存不可访问时返回 false 而不是引发内核的缺页异常
+// 检查地址之后、复制之前，用户内存可能被其它线程解除映射，因此不能只依赖事先的检查
+pub fn copy_user(dst: *mut u8, src: *const u8, len: usize) -> bool {
+    access_user(|| unsafe { __copy_user(dst, src, len) }) == 0
+}
+
+// 内核镜像（包括内核堆）是线性映射的，可以直接换算出物理地址
+pub fn kernel_virt_to_phys(vaddr: usize) -> usize {
+    vaddr - KERNEL_OFFSET + MEMORY_OFFSET
+}
+
+// 线性映射区域中的物理地址对应的虚拟地址
+pub fn phys_to_virt(paddr: usize) -> usize {
+    assert!(paddr >= MEMORY_OFFSET && paddr < MEMORY_OFFSET + KERNEL_LINEAR_SIZE);
+    paddr - MEMORY_OFFSET + KERNEL_OFFSET
+}
+
+// 分配 pages 个物理上连续的页帧，返回它们在线性映射区域中的虚拟地址
+// 线性映射建立之前，或者分配到的页帧不在线性映射区域中时返回 None
+pub fn alloc_kernel_pages(pages: usize) -> Option<usize> {
+    if !LINEAR_MAPPED.load(Ordering::Acquire) {
+        return None;
+    }
+    let frame = alloc_frames(pages)?;
+    let paddr = frame.start_address().as_usize();
+    if paddr + pages * PAGE_SIZE > MEMORY_OFFSET + KERNEL_LINEAR_SIZE {
+        dealloc_frames(frame, pages);
+        return None;
+    }
+    Some(phys_to_virt(paddr))
+}
+
+pub fn dealloc_kernel_pages(vaddr: usize, pages: usize) {
+    let frame = Frame::of_addr(PhysAddr::new(kernel_virt_to_phys(vaddr)));
+    dealloc_frames(frame, pages);
+}
+
+// 打印页帧、内核堆以及各个 slab 缓存的使用情况
+pub fn print_stats() {
+    println!("frames: {} free, {} used", frame_allocator::free_frames(), frame_allocator::used_frames());
+    println!("kernel heap: {:#x} bytes, {:#x} bytes grown", KERNEL_HEAP_SIZE + HEAP_ALLOCATOR.grown(), HEAP_ALLOCATOR.grown());
+    slab::print_stats();
+    let swap = swap::stats();
+    println!("swap: {}/{} slots used, {} swap outs, {} swap ins", swap.used, swap.slots, swap.swap_outs, swap.swap_ins);
+}
+
+// 把 [paddr, paddr + size) 处的设备寄存器映射到内核地址空间，返回对应的虚拟地址
+// 只能在启动阶段、创建用户进程之前调用
+pub fn ioremap(paddr: usize, size: usize) -> usize {
+    use core::sync::atomic::{ AtomicUsize, Ordering };
+    static NEXT: AtomicUsize = AtomicUsize::new(MMIO_START);
+    let offset = paddr & (PAGE_SIZE - 1);
+    let pages = (offset + size + PAGE_SIZE - 1) / PAGE_SIZE;
+    let vaddr = NEXT.fetch_add(pages * PAGE_SIZE, Ordering::Relaxed);
+    assert!(vaddr + pages * PAGE_SIZE <= MMIO_END, "MMIO region exhausted");
+    let mut pt = paging::active_table();
+    for i in 0..pages {
+        let entry = pt.map(vaddr + i * PAGE_SIZE, (paddr - offset) + i * PAGE_SIZE);
+        entry.set_execute(false);
+        entry.update();
+    }
+    vaddr + offset
+}
+
 pub enum PageFault{
     LoadPageFault,
     StorePageFault,
+    InstructionPageFault,
 }
 
 use crate::context::TrapFrame;
 pub fn do_pgfault(tf: &mut TrapFrame, style: PageFault) {
+    // stval 中保存着引发缺页异常的虚拟地址，被换出的页直接换入
+    if swap::handle_page_fault(tf.stval) {
+        return;
+    }
+    if crate::process::handle_page_fault(tf.stval) {
+        return;
+    }
+    if tf.from_user() {
+        crate::interrupt::user_fault(tf, crate::interrupt::SIGSEGV);
+        return;
+    }
+    // copy_user 中的缺页无法处理时从 __copy_user_fault 返回错误
+    if tf.sepc >= __copy_user as usize && tf.sepc < __copy_user_fault as usize {
+        tf.sepc = __copy_user_fault as usize;
+        return;
+    }
     match style {
-        PageFault::LoadPageFault => panic!("load pagefault"),
-        PageFault::StorePageFault => panic!("store pagefault"),
+        PageFault::LoadPageFault => panic!("load pagefault @ {:#x}, pc = {:#x}", tf.stval, tf.sepc),
+        PageFault::StorePageFault => panic!("store pagefault @ {:#x}, pc = {:#x}", tf.stval, tf.sepc),
+        PageFault::InstructionPageFault => panic!("instruction pagefault @ {:#x}, pc = {:#x}", tf.stval, tf.sepc),
     }
 }
 
@@ -70,7 +178,8 @@ extern "C" {
     fn bootstacktop();
 }
 
-fn remap_kernel(dtb: usize) {
+// regions 和 kernel_end 与 frame_allocator::init 的参数相同
+fn remap_kernel(dtb: usize, regions: &[(usize, usize)], kernel_end: usize) {
     let offset = - ( KERNEL_OFFSET as isize - MEMORY_OFFSET as isize);
 
     use crate::memory_set::{ MemorySet, handler::Linear, attr::MemoryAttr };
@@ -111,7 +220,22 @@ fn remap_kernel(dtb: usize) {
         MemoryAttr::new(),
         Linear::new(offset),
     );
+    // 内核之后可以分配的物理内存，内核堆的增长和 slab 都从这里取页帧
+    for &(addr, size) in regions {
+        let start = (addr.max(kernel_end) + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+        let end = (addr + size).min(MEMORY_OFFSET + KERNEL_LINEAR_SIZE) & !(PAGE_SIZE - 1);
+        if start < end {
+            memset.push(
+                phys_to_virt(start),
+                phys_to_virt(end - PAGE_SIZE) + PAGE_SIZE,
+                MemoryAttr::new(),
+                Linear::new(offset),
+            );
+        }
+    }
     unsafe{
         memset.activate();
     }
+    // 内核页表一直在使用，不能被释放
+    core::mem::forget(memset);
 }
@@ -1,4 +1,4 @@
-use crate::consts::{ RECURSIVE_INDEX, PAGE_SIZE };
+use crate::consts::{ RECURSIVE_INDEX, PAGE_SIZE, MMIO_START, MMIO_END, KERNEL_OFFSET, KERNEL_LINEAR_SIZE };
 use riscv::asm::{sfence_vma, sfence_vma_all};
 use riscv::paging::{
     Mapper, PageTable as RvPageTable, PageTableEntry, PageTableFlags as EF, 
@@ -7,6 +7,7 @@ use riscv::paging::{
 use riscv::register::satp;
 use riscv::addr::*;
 use super::frame_allocator::{alloc_frame, dealloc_frame};
+use super::phys_to_virt;
 
 const TEMP_PAGE_ADDR: usize = 0xcafeb000;    // 临时挂靠的地址
 const ROOT_PAGE_TABLE: *mut RvPageTable =
@@ -51,6 +52,10 @@ impl PageEntry {
         self.0.flags_mut().set(EF::VALID | EF::READABLE, value);
     }
 
+    pub fn clear(&mut self) {
+        self.0.set_unused();
+    }
+
     pub fn target(&self) -> usize {
         self.0.addr().as_usize()
     }
@@ -74,6 +79,22 @@ impl PageEntry {
         self.0.flags_mut().set(EF::EXECUTABLE, value);
     }
 
+    // 写时复制标记，使用页表项中保留给软件的 RSW 位
+    pub fn cow(&self) -> bool {
+        self.0.flags().contains(EF::RESERVED1)
+    }
+    pub fn set_cow(&mut self, value: bool) {
+        self.0.flags_mut().set(EF::RESERVED1, value);
+    }
+
+    // 已被换出的页，页表项不存在，target 为交换区中的位置
+    pub fn swapped(&self) -> bool {
+        self.0.flags().contains(EF::RESERVED2)
+    }
+    pub fn set_swapped(&mut self, value: bool) {
+        self.0.flags_mut().set(EF::RESERVED2, value);
+    }
+
 }
 
 impl ActivePageTable {
@@ -101,7 +122,13 @@ impl ActivePageTable {
         flush.flush();
     }
 
-    fn get_entry(&mut self, vaddr: usize) -> Option<&mut PageEntry> {   // 类似get_pte
+    // 当前正在访问的页表的 satp，在 InactivePageTable::edit 中为被编辑的页表
+    pub fn token(&self) -> usize {
+        let root = unsafe { &*ROOT_PAGE_TABLE };
+        root[RECURSIVE_INDEX].frame().number() | (1 << 31)
+    }
+
+    pub fn get_entry(&mut self, vaddr: usize) -> Option<&mut PageEntry> {   // 类似get_pte
         let page = Page::of_addr(VirtAddr::new(vaddr));
         if let Ok(e) = self.0.ref_entry(page.clone()) {
             let e = unsafe { &mut *(e as *mut PageTableEntry) };
@@ -134,6 +161,18 @@ pub fn active_table() -> ActivePageTable {
     unsafe{ ActivePageTable::new() }
 }
 
+// 通过线性映射直接访问 satp 为 token 的页表中 vaddr 的页表项，不需要切换或编辑页表
+// 二级页表不存在时返回 None
+pub fn entry_of(token: usize, vaddr: usize) -> Option<PageEntry> {
+    let root = unsafe { &mut *(phys_to_virt((token & !(1 << 31)) * PAGE_SIZE) as *mut RvPageTable) };
+    if !root[vaddr >> 22].flags().contains(EF::VALID) {
+        return None;
+    }
+    let table = unsafe { &mut *(phys_to_virt(root[vaddr >> 22].addr().as_usize()) as *mut RvPageTable) };
+    let page = Page::of_addr(VirtAddr::new(vaddr));
+    Some(PageEntry(&mut table[(vaddr >> 12) & 0x3ff], page))
+}
+
 #[derive(Debug)]
 pub struct InactivePageTable {
     root_frame: Frame,
@@ -256,24 +295,50 @@ impl InactivePageTable {
 
     pub fn map_kernel(&mut self) {
         let table = unsafe { &mut *ROOT_PAGE_TABLE };
-        extern "C" {
-            fn start();
-            fn end();
-        }
-        let mut entrys: [PageTableEntry; 16] = unsafe { core::mem::uninitialized() };
-        let entry_start = start as usize >> 22;
-        let entry_end = (end as usize >> 22) + 1;
-        let entry_count = entry_end - entry_start;
+        // 线性映射区域（包括内核镜像）的二级页表在启动时全部建立，之后所有页表共享
+        let mut entrys: [PageTableEntry; KERNEL_LINEAR_SIZE >> 22] = unsafe { core::mem::uninitialized() };
+        let entry_start = KERNEL_OFFSET >> 22;
+        let entry_count = KERNEL_LINEAR_SIZE >> 22;
         for i in 0..entry_count {
             entrys[i] = table[entry_start + i];
         }
 
+        // 设备寄存器的映射在启动时建立，之后所有页表共享
+        let mmio_start = MMIO_START >> 22;
+        let mmio_end = MMIO_END >> 22;
+        let mut mmio_entrys: [PageTableEntry; 48] = unsafe { core::mem::uninitialized() };
+        for i in mmio_start..mmio_end {
+            mmio_entrys[i - mmio_start] = table[i];
+        }
+
         self.edit(|_| {
             // NOTE: 'table' now refers to new page table
             for i in 0..entry_count {
                 table[entry_start + i] = entrys[i];
             }
+            for i in mmio_start..mmio_end {
+                table[i] = mmio_entrys[i - mmio_start];
+            }
+        });
+    }
+}
+
+// 释放根页表以及只属于这个页表的二级页表：用户地址和临时映射地址对应的二级页表
+// 内核和设备寄存器部分的二级页表是所有页表共享的，不能释放
+impl Drop for InactivePageTable {
+    fn drop(&mut self) {
+        assert_ne!(Self::active_token(), self.token(), "can not drop the active page table");
+        active_table().with_temporary_map(self.root_frame.start_address(), |_, table : &mut RvPageTable|{
+            let user = 0..(KERNEL_OFFSET >> 22);
+            let temp = (TEMP_PAGE_ADDR >> 22)..((TEMP_PAGE_ADDR >> 22) + 1);
+            for idx in user.chain(temp) {
+                if table[idx].flags().contains(EF::VALID) {
+                    dealloc_frame(table[idx].frame());
+                    table[idx].set_unused();
+                }
+            }
         });
+        dealloc_frame(self.root_frame.clone());
     }
 }
 
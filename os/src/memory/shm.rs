@@ -0,0 +1,86 @@
+use alloc::{ collections::BTreeMap, sync::{ Arc, Weak }, vec::Vec };
+use core::slice;
+use lazy_static::*;
+use riscv::addr::{ Frame, PhysAddr };
+use spin::Mutex;
+use crate::consts::PAGE_SIZE;
+use super::frame_allocator::{ alloc_frame, release_frame };
+use super::phys_to_virt;
+
+// 共享内存段：一组在第一次被访问时才分配的页帧，可以同时映射到多个地址空间中
+// 段本身持有每个页帧的一个引用，每个映射再各持有一个，最后一个持有者释放时页帧才被回收
+pub struct ShmSegment {
+    key: usize,
+    size: usize,
+    frames: Mutex<Vec<Option<usize>>>,  // 每一页的物理地址
+}
+
+impl ShmSegment {
+    pub fn key(&self) -> usize {
+        self.key
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    // 段中第 index 页的物理地址，还没有分配时分配一个清零的页帧
+    pub fn frame(&self, index: usize) -> usize {
+        let mut frames = self.frames.lock();
+        if let Some(target) = frames[index] {
+            return target;
+        }
+        let target = alloc_frame().expect("failed to allocate frame").start_address().as_usize();
+        unsafe { slice::from_raw_parts_mut(phys_to_virt(target) as *mut u8, PAGE_SIZE) }
+            .iter_mut()
+            .for_each(|x| *x = 0);
+        frames[index] = Some(target);
+        target
+    }
+}
+
+impl Drop for ShmSegment {
+    fn drop(&mut self) {
+        for &target in self.frames.lock().iter().flatten() {
+            release_frame(Frame::of_addr(PhysAddr::new(target)));
+        }
+    }
+}
+
+// 键为 0 的段是私有的，不会被其它进程通过键找到
+pub const IPC_PRIVATE: usize = 0;
+
+// 键 -> 共享内存段。这里不持有段，没有进程使用的段会被释放
+lazy_static! {
+    static ref SEGMENTS: Mutex<BTreeMap<usize, Weak<ShmSegment>>> = Mutex::new(BTreeMap::new());
+}
+
+// 查找键为 key 的段
+pub fn find(key: usize) -> Option<Arc<ShmSegment>> {
+    if key == IPC_PRIVATE {
+        return None;
+    }
+    let mut segments = SEGMENTS.lock();
+    let segment = segments.get(&key)?.upgrade();
+    if segment.is_none() {
+        segments.remove(&key);
+    }
+    segment
+}
+
+// 创建一个大小为 size 的段，size 需要按页对齐。键为 key 的段已经存在时返回 None
+pub fn create(key: usize, size: usize) -> Option<Arc<ShmSegment>> {
+    let segment = Arc::new(ShmSegment {
+        key,
+        size,
+        frames: Mutex::new((0..size / PAGE_SIZE).map(|_| None).collect()),
+    });
+    if key != IPC_PRIVATE {
+        let mut segments = SEGMENTS.lock();
+        if segments.get(&key).and_then(Weak::upgrade).is_some() {
+            return None;
+        }
+        segments.insert(key, Arc::downgrade(&segment));
+    }
+    Some(segment)
+}
@@ -0,0 +1,212 @@
+use alloc::{ collections::{ BTreeMap, BTreeSet }, vec::Vec };
+use core::mem::{ size_of, align_of };
+use core::sync::atomic::{ AtomicUsize, Ordering };
+use lazy_static::*;
+use spin::Mutex;
+use crate::consts::PAGE_SIZE;
+use crate::HEAP_ALLOCATOR;
+use crate::sync::spinlock::IrqSpinLock;
+use super::{ alloc_kernel_pages, dealloc_kernel_pages };
+
+// 每个 slab 至少能容纳的对象数
+const MIN_OBJECTS: usize = 4;
+
+// 一个 slab 是一段物理上连续的页，被切分成若干个大小相同的对象
+struct Slab {
+    free: Vec<usize>,   // 空闲对象的下标
+}
+
+struct SlabInner {
+    slabs: BTreeMap<usize, Slab>,   // 起始地址 -> slab
+    partial: BTreeSet<usize>,       // 还有空闲对象的 slab 的起始地址
+    stats: SlabStats,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SlabStats {
+    pub slabs: usize,   // 当前的 slab 数
+    pub in_use: usize,  // 已经分配出去的对象数
+    pub allocs: usize,  // 累计分配的次数
+    pub frees: usize,   // 累计释放的次数
+}
+
+// 固定大小对象的缓存，slab 的页帧直接从页帧分配器中取得
+pub struct SlabCache {
+    name: &'static str,
+    size: usize,    // 对齐之后的对象大小
+    pages: usize,   // 每个 slab 的页数
+    count: usize,   // 每个 slab 中的对象数
+    ctor: Option<fn(usize)>,
+    inner: IrqSpinLock<SlabInner>,
+}
+
+impl SlabCache {
+    // ctor 在创建 slab 时对其中的每个对象调用一次，参数为对象的地址
+    // 对象释放时应当恢复到构造之后的状态，再次分配时不会重新构造
+    pub fn new(name: &'static str, size: usize, align: usize, ctor: Option<fn(usize)>) -> Self {
+        assert!(align.is_power_of_two() && align <= PAGE_SIZE, "invalid slab align");
+        let size = (size.max(1) + align - 1) & !(align - 1);
+        let pages = ((size * MIN_OBJECTS + PAGE_SIZE - 1) / PAGE_SIZE).next_power_of_two();
+        SlabCache {
+            name,
+            size,
+            pages,
+            count: pages * PAGE_SIZE / size,
+            ctor,
+            inner: IrqSpinLock::new(SlabInner {
+                slabs: BTreeMap::new(),
+                partial: BTreeSet::new(),
+                stats: SlabStats::default(),
+            }),
+        }
+    }
+
+    // 存放 T 类型对象的缓存
+    pub fn of<T>(name: &'static str, ctor: Option<fn(usize)>) -> Self {
+        Self::new(name, size_of::<T>(), align_of::<T>(), ctor)
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn object_size(&self) -> usize {
+        self.size
+    }
+
+    pub fn stats(&self) -> SlabStats {
+        self.inner.lock().stats
+    }
+
+    // 分配一个对象，返回它的地址。没有空闲的页帧时返回 None
+    pub fn alloc(&self) -> Option<usize> {
+        let mut guard = self.inner.lock();
+        let inner = &mut *guard;
+        let base = match inner.partial.iter().next().cloned() {
+            Some(base) => base,
+            None => self.grow(inner)?,
+        };
+        let slab = inner.slabs.get_mut(&base).unwrap();
+        let idx = slab.free.pop().unwrap();
+        if slab.free.is_empty() {
+            inner.partial.remove(&base);
+        }
+        inner.stats.in_use += 1;
+        inner.stats.allocs += 1;
+        Some(base + idx * self.size)
+    }
+
+    pub fn dealloc(&self, addr: usize) {
+        let mut guard = self.inner.lock();
+        let inner = &mut *guard;
+        let (base, empty) = {
+            let (&base, slab) = inner.slabs
+                .range_mut(..=addr)
+                .next_back()
+                .expect("slab dealloc: invalid address");
+            let offset = addr - base;
+            assert!(
+                offset < self.count * self.size && offset % self.size == 0,
+                "{}: dealloc invalid address {:#x}", self.name, addr
+            );
+            let idx = offset / self.size;
+            assert!(!slab.free.contains(&idx), "{}: double free {:#x}", self.name, addr);
+            slab.free.push(idx);
+            (base, slab.free.len() == self.count)
+        };
+        inner.partial.insert(base);
+        inner.stats.in_use -= 1;
+        inner.stats.frees += 1;
+        // 保留一个完全空闲的 slab，避免反复分配和释放同一个对象时频繁地申请页帧
+        if empty && inner.partial.iter().any(|&other| other != base && self.is_empty(inner, other)) {
+            self.release(inner, base);
+        }
+    }
+
+    // 把所有完全空闲的 slab 归还给页帧分配器，返回归还的 slab 数
+    pub fn shrink(&self) -> usize {
+        let mut guard = self.inner.lock();
+        let inner = &mut *guard;
+        let empty: Vec<usize> = inner.partial
+            .iter()
+            .cloned()
+            .filter(|&base| self.is_empty(inner, base))
+            .collect();
+        for &base in empty.iter() {
+            self.release(inner, base);
+        }
+        empty.len()
+    }
+
+    fn is_empty(&self, inner: &SlabInner, base: usize) -> bool {
+        inner.slabs[&base].free.len() == self.count
+    }
+
+    // 创建一个新的 slab，返回它的起始地址
+    fn grow(&self, inner: &mut SlabInner) -> Option<usize> {
+        let base = alloc_kernel_pages(self.pages)?;
+        if let Some(ctor) = self.ctor {
+            for i in 0..self.count {
+                ctor(base + i * self.size);
+            }
+        }
+        inner.slabs.insert(base, Slab { free: (0..self.count).rev().collect() });
+        inner.partial.insert(base);
+        inner.stats.slabs += 1;
+        Some(base)
+    }
+
+    fn release(&self, inner: &mut SlabInner, base: usize) {
+        inner.partial.remove(&base);
+        inner.slabs.remove(&base);
+        inner.stats.slabs -= 1;
+        dealloc_kernel_pages(base, self.pages);
+    }
+}
+
+// 注册过的缓存，用于打印统计信息
+lazy_static! {
+    static ref CACHES: Mutex<Vec<&'static SlabCache>> = Mutex::new(Vec::new());
+}
+
+pub fn register(cache: &'static SlabCache) {
+    CACHES.lock().push(cache);
+}
+
+pub fn print_stats() {
+    for cache in CACHES.lock().iter() {
+        let stats = cache.stats();
+        println!(
+            "slab {}: object size {:#x}, {} slabs, {} in use, {} allocs, {} frees",
+            cache.name(), cache.object_size(), stats.slabs, stats.in_use, stats.allocs, stats.frees
+        );
+    }
+}
+
+pub fn test() {
+    static CONSTRUCTED: AtomicUsize = AtomicUsize::new(0);
+    fn ctor(addr: usize) {
+        unsafe { *(addr as *mut usize) = addr; }
+        CONSTRUCTED.fetch_add(1, Ordering::Relaxed);
+    }
+    // 测试期间内核堆可能增长，增长用掉的页不会归还，比较时要扣除
+    let free = super::frame_allocator::free_frames();
+    let grown = HEAP_ALLOCATOR.grown();
+    let cache = SlabCache::of::<[usize; 100]>("test", Some(ctor));
+    let mut objects = Vec::new();
+    for _ in 0..cache.count * 2 + 1 {
+        let addr = cache.alloc().expect("test slab: failed to alloc");
+        assert_eq!(unsafe { *(addr as *const usize) }, addr);
+        objects.push(addr);
+    }
+    assert_eq!(cache.stats().slabs, 3);
+    assert_eq!(CONSTRUCTED.load(Ordering::Relaxed), cache.count * 3);
+    for &addr in objects.iter() {
+        cache.dealloc(addr);
+    }
+    assert_eq!(cache.stats().slabs, 1);
+    cache.shrink();
+    let grown = (HEAP_ALLOCATOR.grown() - grown) / PAGE_SIZE;
+    assert_eq!(super::frame_allocator::free_frames() + grown, free);
+    println!("test slab: {} objects in {} pages per slab", cache.count, cache.pages);
+}
@@ -0,0 +1,45 @@
+use alloc::vec::Vec;
+use super::SwapPolicy;
+
+// 时钟算法：指针循环扫描页帧，访问位为 1 的清零后跳过，换出第一个访问位为 0 的页帧
+pub struct ClockPolicy {
+    frames: Vec<usize>,
+    hand: usize,
+}
+
+impl ClockPolicy {
+    pub fn new() -> Self {
+        ClockPolicy { frames: Vec::new(), hand: 0 }
+    }
+}
+
+impl SwapPolicy for ClockPolicy {
+    // 新的页帧放在指针之前，最后才会被扫描到
+    fn insert(&mut self, frame: usize) {
+        self.frames.insert(self.hand, frame);
+        self.hand += 1;
+    }
+
+    fn remove(&mut self, frame: usize) {
+        if let Some(idx) = self.frames.iter().position(|&f| f == frame) {
+            self.frames.remove(idx);
+            if idx < self.hand {
+                self.hand -= 1;
+            }
+        }
+    }
+
+    // 最多扫描两圈，第一圈清除的访问位在第二圈时都为 0
+    fn choose_victim(&mut self, accessed: &mut FnMut(usize) -> Option<bool>) -> Option<usize> {
+        for _ in 0..self.frames.len() * 2 {
+            if self.hand >= self.frames.len() {
+                self.hand = 0;
+            }
+            match accessed(self.frames[self.hand]) {
+                Some(false) => return Some(self.frames.remove(self.hand)),
+                _ => self.hand += 1,
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,28 @@
+use alloc::collections::VecDeque;
+use super::SwapPolicy;
+
+// 先进先出：换出最早被映射的页帧
+pub struct FifoPolicy {
+    queue: VecDeque<usize>,
+}
+
+impl FifoPolicy {
+    pub fn new() -> Self {
+        FifoPolicy { queue: VecDeque::new() }
+    }
+}
+
+impl SwapPolicy for FifoPolicy {
+    fn insert(&mut self, frame: usize) {
+        self.queue.push_back(frame);
+    }
+
+    fn remove(&mut self, frame: usize) {
+        self.queue.retain(|&f| f != frame);
+    }
+
+    fn choose_victim(&mut self, accessed: &mut FnMut(usize) -> Option<bool>) -> Option<usize> {
+        let idx = self.queue.iter().position(|&frame| accessed(frame).is_some())?;
+        self.queue.remove(idx)
+    }
+}
@@ -0,0 +1,339 @@
+use alloc::{ boxed::Box, collections::BTreeMap, sync::Arc, vec::Vec };
+use core::slice;
+use core::sync::atomic::{ AtomicBool, Ordering };
+use lazy_static::*;
+use rcore_fs::dev::{ self, Device, DevError };
+use rcore_fs::vfs::{ FileType, INode };
+use riscv::addr::{ Frame, PhysAddr };
+use crate::consts::PAGE_SIZE;
+use crate::sync::{ futex, spinlock::IrqSpinLock };
+use super::paging::{ ActivePageTable, active_table, entry_of };
+use super::frame_allocator::{ alloc_frame, dealloc_frame, frame_ref_count };
+use super::phys_to_virt;
+
+mod fifo;
+mod clock;
+mod ws;
+
+pub use self::fifo::FifoPolicy;
+pub use self::clock::ClockPolicy;
+pub use self::ws::WorkingSetPolicy;
+
+// 没有第二块磁盘时在文件系统中创建的交换文件及其大小
+const SWAP_FILE: &str = "swap";
+const SWAP_FILE_SIZE: usize = 0x40_0000;
+
+// 页面置换算法的接口，管理所有可以换出的页帧，页帧用页帧号表示
+pub trait SwapPolicy: Send {
+    // 页帧被映射到用户地址空间中，成为换出的候选
+    fn insert(&mut self, frame: usize);
+    // 页帧不再被映射
+    fn remove(&mut self, frame: usize);
+    // 选出一个换出的页帧并把它从候选中移除
+    // accessed 返回页帧最近是否被访问过并清除访问位，返回 None 表示该页帧暂时不能换出
+    fn choose_victim(&mut self, accessed: &mut FnMut(usize) -> Option<bool>) -> Option<usize>;
+}
+
+// 根据启动参数中的 swap=fifo|clock|ws 选择页面置换算法，默认为 Clock
+pub fn from_bootargs(bootargs: &str) -> Box<SwapPolicy> {
+    let name = bootargs
+        .split_whitespace()
+        .find(|arg| arg.starts_with("swap="))
+        .map_or("clock", |arg| &arg["swap=".len()..]);
+    match name {
+        "fifo" => {
+            println!("swap policy: fifo");
+            Box::new(FifoPolicy::new())
+        },
+        "ws" => {
+            println!("swap policy: working set");
+            Box::new(WorkingSetPolicy::new())
+        },
+        _ => {
+            println!("swap policy: clock");
+            Box::new(ClockPolicy::new())
+        },
+    }
+}
+
+// 把交换文件当作设备使用
+struct FileDevice(Arc<INode>);
+
+impl Device for FileDevice {
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> dev::Result<usize> {
+        self.0.read_at(offset, buf).map_err(|_| DevError)
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> dev::Result<usize> {
+        self.0.write_at(offset, buf).map_err(|_| DevError)
+    }
+
+    fn sync(&self) -> dev::Result<()> {
+        self.0.sync_data().map_err(|_| DevError)
+    }
+}
+
+// 交换区按页划分成若干个槽
+struct SwapArea {
+    device: Arc<Device>,
+    free: Vec<usize>,   // 空闲的槽
+    total: usize,
+}
+
+impl SwapArea {
+    fn new(device: Arc<Device>, size: usize) -> Self {
+        let total = size / PAGE_SIZE;
+        SwapArea { device, free: (0..total).rev().collect(), total }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SwapStats {
+    pub slots: usize,       // 交换区的总槽数
+    pub used: usize,        // 正在使用的槽数
+    pub swap_outs: usize,   // 累计换出的页数
+    pub swap_ins: usize,    // 累计换入的页数
+}
+
+// 正在写入交换区的页。写入期间不持有锁，页可能被换回或解除映射
+struct Writeback {
+    slot: usize,
+    frame: usize,
+    token: usize,
+    addr: usize,
+    claimed: bool,  // 已经被 swap_in 取回
+    freed: bool,    // 槽已经被释放
+}
+
+struct SwapManager {
+    area: Option<SwapArea>,
+    writeback: Option<Writeback>,
+    policy: Box<SwapPolicy>,
+    // 反向映射：用户页帧号 -> 映射了它的 (页表的 satp, 虚拟地址)
+    rmap: BTreeMap<usize, Vec<(usize, usize)>>,
+    stats: SwapStats,
+}
+
+lazy_static! {
+    static ref SWAP: IrqSpinLock<SwapManager> = IrqSpinLock::new(SwapManager {
+        area: None,
+        writeback: None,
+        policy: Box::new(ClockPolicy::new()),
+        rmap: BTreeMap::new(),
+        stats: SwapStats::default(),
+    });
+}
+
+// 写交换区时可能会分配页帧，此时不能再次换出
+static SWAPPING: AtomicBool = AtomicBool::new(false);
+
+// 使用第二块 virtio 磁盘作为交换区，没有时使用文件系统中的交换文件
+pub fn init(bootargs: &str) {
+    let policy = from_bootargs(bootargs);
+    let area = match crate::drivers::virtio_blk::take() {
+        Some(blk) => {
+            let size = blk.capacity();
+            println!("swap on virtio-blk: {:#x} bytes", size);
+            Some(SwapArea::new(Arc::new(blk), size))
+        },
+        None => swap_file().map(|inode| {
+            println!("swap on file /{}: {:#x} bytes", SWAP_FILE, SWAP_FILE_SIZE);
+            SwapArea::new(Arc::new(FileDevice(inode)), SWAP_FILE_SIZE)
+        }),
+    };
+    if area.is_none() {
+        println!("no swap area");
+    }
+    let mut swap = SWAP.lock();
+    swap.stats.slots = area.as_ref().map_or(0, |area| area.total);
+    swap.area = area;
+    swap.policy = policy;
+}
+
+fn swap_file() -> Option<Arc<INode>> {
+    let root = &crate::fs::ROOT_INODE;
+    let inode = match root.find(SWAP_FILE) {
+        Ok(inode) => inode,
+        Err(_) => root.create(SWAP_FILE, FileType::File, 0o600).ok()?,
+    };
+    inode.resize(SWAP_FILE_SIZE).ok()?;
+    Some(inode)
+}
+
+// 用户页 addr 被映射到页帧 target，pt 为它所在的页表
+pub fn add_mapping(pt: &ActivePageTable, addr: usize, target: usize) {
+    let frame = target / PAGE_SIZE;
+    let mut swap = SWAP.lock();
+    let maps = swap.rmap.entry(frame).or_insert_with(Vec::new);
+    maps.push((pt.token(), addr & !(PAGE_SIZE - 1)));
+    if maps.len() == 1 {
+        swap.policy.insert(frame);
+    }
+}
+
+// 取消用户页 addr 到页帧 target 的映射
+pub fn remove_mapping(pt: &ActivePageTable, addr: usize, target: usize) {
+    let frame = target / PAGE_SIZE;
+    let key = (pt.token(), addr & !(PAGE_SIZE - 1));
+    let mut swap = SWAP.lock();
+    let empty = match swap.rmap.get_mut(&frame) {
+        Some(maps) => {
+            maps.retain(|&map| map != key);
+            maps.is_empty()
+        },
+        None => return,
+    };
+    if empty {
+        swap.rmap.remove(&frame);
+        swap.policy.remove(frame);
+    }
+}
+
+// 释放被换出的页占用的槽
+pub fn free_slot(slot: usize) {
+    let mut swap = SWAP.lock();
+    if let Some(wb) = swap.writeback.as_mut() {
+        if wb.slot == slot && !wb.claimed {
+            wb.freed = true;
+        }
+    }
+    swap.area.as_mut().expect("no swap area").free.push(slot);
+    swap.stats.used -= 1;
+}
+
+// 换出一页，返回是否成功。只换出没有被共享、也没有 futex 等待者的页
+// 写交换区时不持有 SWAP 的锁，不会在整个传输期间关闭中断
+pub fn swap_out() -> bool {
+    if SWAPPING.swap(true, Ordering::Relaxed) {
+        return false;
+    }
+    let ok = swap_out_inner();
+    SWAPPING.store(false, Ordering::Relaxed);
+    ok
+}
+
+fn swap_out_inner() -> bool {
+    // 先选出换出的页并在页表中把它标记为已换出，页帧在写完之前仍然保留
+    let (device, slot, frame) = {
+        let mut guard = SWAP.lock();
+        let swap = &mut *guard;
+        let area = match swap.area.as_mut() {
+            Some(area) => area,
+            None => return false,
+        };
+        let slot = match area.free.pop() {
+            Some(slot) => slot,
+            None => return false,
+        };
+        let rmap = &swap.rmap;
+        let victim = swap.policy.choose_victim(&mut |frame| {
+            let &(token, addr) = match rmap.get(&frame) {
+                Some(maps) if maps.len() == 1 => &maps[0],
+                _ => return None,
+            };
+            if frame_ref_count(&Frame::of_addr(PhysAddr::new(frame * PAGE_SIZE))) != 1 || futex::has_waiters(frame * PAGE_SIZE) {
+                return None;
+            }
+            let mut entry = entry_of(token, addr)?;
+            let accessed = entry.accessed();
+            entry.clear_accessed();
+            entry.update();
+            Some(accessed)
+        });
+        let frame = match victim {
+            Some(frame) => frame,
+            None => {
+                area.free.push(slot);
+                return false;
+            },
+        };
+        let device = area.device.clone();
+        let (token, addr) = swap.rmap.remove(&frame).unwrap()[0];
+        let mut entry = entry_of(token, addr).expect("fail to get entry");
+        entry.set_present(false);
+        entry.set_swapped(true);
+        entry.set_target(slot * PAGE_SIZE);
+        entry.update();
+        swap.stats.used += 1;
+        swap.writeback = Some(Writeback { slot, frame, token, addr, claimed: false, freed: false });
+        (device, slot, frame)
+    };
+    let data = unsafe { slice::from_raw_parts(phys_to_virt(frame * PAGE_SIZE) as *const u8, PAGE_SIZE) };
+    let written = device.write_at(slot * PAGE_SIZE, data) == Ok(PAGE_SIZE);
+    let mut swap = SWAP.lock();
+    let wb = swap.writeback.take().unwrap();
+    // 写入期间页已经被换回，页帧仍在使用
+    if wb.claimed {
+        return true;
+    }
+    // 写入期间页已经被解除映射，槽已经释放
+    if wb.freed {
+        dealloc_frame(Frame::of_addr(PhysAddr::new(frame * PAGE_SIZE)));
+        return true;
+    }
+    if !written {
+        println!("swap: failed to write slot {}", slot);
+        let mut entry = entry_of(wb.token, wb.addr).expect("fail to get entry");
+        entry.set_swapped(false);
+        entry.set_present(true);
+        entry.set_target(frame * PAGE_SIZE);
+        entry.update();
+        swap.rmap.entry(frame).or_insert_with(Vec::new).push((wb.token, wb.addr));
+        swap.policy.insert(frame);
+        swap.area.as_mut().unwrap().free.push(slot);
+        swap.stats.used -= 1;
+        return false;
+    }
+    swap.stats.swap_outs += 1;
+    dealloc_frame(Frame::of_addr(PhysAddr::new(frame * PAGE_SIZE)));
+    true
+}
+
+// 把 pt 中被换出的页 addr 换入，不是被换出的页时返回 false
+pub fn swap_in(pt: &mut ActivePageTable, addr: usize) -> bool {
+    match pt.get_entry(addr) {
+        Some(ref entry) if entry.swapped() => {},
+        _ => return false,
+    }
+    // 正在写入交换区的页直接取回原来的页帧
+    let slot = pt.get_entry(addr).unwrap().target() / PAGE_SIZE;
+    let pending = {
+        let mut swap = SWAP.lock();
+        match swap.writeback.as_mut() {
+            Some(wb) if wb.slot == slot && !wb.freed => {
+                wb.claimed = true;
+                Some(wb.frame * PAGE_SIZE)
+            },
+            _ => None,
+        }
+    };
+    let target = match pending {
+        Some(target) => target,
+        None => {
+            // 先分配页帧，分配时可能会换出其它页
+            let target = alloc_frame().expect("failed to allocate frame").start_address().as_usize();
+            let data = unsafe { slice::from_raw_parts_mut(phys_to_virt(target) as *mut u8, PAGE_SIZE) };
+            let device = SWAP.lock().area.as_ref().expect("no swap area").device.clone();
+            assert!(device.read_at(slot * PAGE_SIZE, data) == Ok(PAGE_SIZE), "swap: failed to read slot {}", slot);
+            target
+        },
+    };
+    let entry = pt.get_entry(addr).expect("fail to get entry");
+    entry.set_target(target);
+    entry.set_swapped(false);
+    entry.set_present(true);
+    entry.update();
+    free_slot(slot);
+    SWAP.lock().stats.swap_ins += 1;
+    add_mapping(pt, addr, target);
+    true
+}
+
+// 访问当前页表中被换出的页引起的缺页异常，不需要查找 MemorySet
+pub fn handle_page_fault(addr: usize) -> bool {
+    swap_in(&mut active_table(), addr)
+}
+
+pub fn stats() -> SwapStats {
+    SWAP.lock().stats
+}
@@ -0,0 +1,64 @@
+use alloc::vec::Vec;
+use crate::clock::get_tick;
+use super::SwapPolicy;
+
+// 工作集窗口的大小，单位为时钟中断的次数
+const WINDOW: usize = 50;
+
+// 工作集算法：扫描时记录每个页帧最近一次被访问的时间
+// 换出第一个在窗口内没有被访问过的页帧，都在工作集中时换出最久没有被访问的页帧
+pub struct WorkingSetPolicy {
+    frames: Vec<(usize, usize)>,    // (页帧号, 最近一次被访问的时间)
+    hand: usize,
+}
+
+impl WorkingSetPolicy {
+    pub fn new() -> Self {
+        WorkingSetPolicy { frames: Vec::new(), hand: 0 }
+    }
+}
+
+impl SwapPolicy for WorkingSetPolicy {
+    fn insert(&mut self, frame: usize) {
+        self.frames.insert(self.hand, (frame, get_tick()));
+        self.hand += 1;
+    }
+
+    fn remove(&mut self, frame: usize) {
+        if let Some(idx) = self.frames.iter().position(|&(f, _)| f == frame) {
+            self.frames.remove(idx);
+            if idx < self.hand {
+                self.hand -= 1;
+            }
+        }
+    }
+
+    fn choose_victim(&mut self, accessed: &mut FnMut(usize) -> Option<bool>) -> Option<usize> {
+        let now = get_tick();
+        let mut oldest: Option<usize> = None;
+        for _ in 0..self.frames.len() {
+            if self.hand >= self.frames.len() {
+                self.hand = 0;
+            }
+            let (frame, last_use) = self.frames[self.hand];
+            match accessed(frame) {
+                Some(true) => self.frames[self.hand].1 = now,
+                Some(false) if now - last_use > WINDOW => return Some(self.frames.remove(self.hand).0),
+                Some(false) => {},
+                None => {
+                    self.hand += 1;
+                    continue;
+                },
+            }
+            if oldest.map_or(true, |idx| self.frames[idx].1 > self.frames[self.hand].1) {
+                oldest = Some(self.hand);
+            }
+            self.hand += 1;
+        }
+        let idx = oldest?;
+        if idx < self.hand {
+            self.hand -= 1;
+        }
+        Some(self.frames.remove(idx).0)
+    }
+}
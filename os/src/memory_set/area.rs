@@ -1,7 +1,8 @@
-use alloc::boxed::Box;
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
 use crate::memory::paging::{ActivePageTable, PageRange,};
 use super::{attr::MemoryAttr, handler::MemoryHandler, };
 use crate::consts::PAGE_SIZE;
+use crate::memory::shm::ShmSegment;
 
 #[derive(Debug,Clone)]
 pub struct MemoryArea {
@@ -18,12 +19,97 @@ impl MemoryArea {
         }
     }
 
-    fn unmap(&self, pt : &mut ActivePageTable) {
+    pub fn unmap(&self, pt : &mut ActivePageTable) {
         for page in PageRange::new(self.start, self.end) {
             self.handler.unmap(pt, page);
         }
     }
 
+    // 修改整个区域的访问权限
+    pub fn protect(&mut self, pt : &mut ActivePageTable, attr : MemoryAttr) {
+        for page in PageRange::new(self.start, self.end) {
+            if let Some(entry) = pt.get_entry(page) {
+                attr.protect(entry);
+            }
+        }
+        self.attr = attr;
+    }
+
+    pub fn contains(&self, addr : usize) -> bool {
+        self.is_overlap_with(addr, addr + 1)
+    }
+
+    // fork 时在原页表中逐页处理，返回每一页需要共享给子进程的物理地址
+    pub fn share(&self, pt : &mut ActivePageTable) -> Vec<(usize, Option<usize>)> {
+        PageRange::new(self.start, self.end)
+            .map(|page| (page, self.handler.share(pt, page)))
+            .collect()
+    }
+
+    pub fn clone_map(&self, pt : &mut ActivePageTable, targets : &[(usize, Option<usize>)]) {
+        for &(page, target) in targets {
+            self.handler.clone_map(pt, page, target, &self.attr);
+        }
+    }
+
+    pub fn handle_page_fault(&self, pt : &mut ActivePageTable, addr : usize) -> bool {
+        // 不可访问或只读区域中已经存在的页不会因为缺页而需要处理，属于非法访问
+        if !self.attr.is_user() || self.attr.is_readonly() {
+            match pt.get_entry(addr) {
+                Some(ref entry) if entry.present() => return false,
+                _ => {},
+            }
+        }
+        self.handler.handle_page_fault(pt, addr)
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    // 在 addr 处把区域一分为二，自身保留 [start, addr)，返回 [addr, end)
+    pub fn split(&mut self, addr : usize) -> MemoryArea {
+        assert!(self.start < addr && addr < self.end, "invalid split address");
+        let right = MemoryArea::new(addr, self.end, self.handler.clone(), self.attr.clone());
+        self.end = addr;
+        right
+    }
+
+    // 把 [start, end) 中的修改写回，调用时该页表必须是当前页表
+    pub fn sync(&self, pt : &mut ActivePageTable, start : usize, end : usize) {
+        for page in PageRange::new(self.start.max(start), self.end.min(end)) {
+            self.handler.sync(pt, page);
+        }
+    }
+
+    pub fn is_shared(&self) -> bool {
+        self.handler.is_shared()
+    }
+
+    pub fn is_guard(&self) -> bool {
+        self.handler.is_guard()
+    }
+
+    pub fn can_write(&self) -> bool {
+        self.handler.can_write()
+    }
+
+    pub fn shm_addr(&self) -> Option<usize> {
+        self.handler.shm_addr()
+    }
+
+    pub fn shm_segment(&self) -> Option<Arc<ShmSegment>> {
+        self.handler.shm_segment()
+    }
+
+    pub fn attr(&self) -> &MemoryAttr {
+        &self.attr
+    }
+
     pub fn is_overlap_with(&self, start_addr : usize, end_addr : usize) -> bool {
         let p1 = self.start / PAGE_SIZE;
         let p2 = (self.end - 1) / PAGE_SIZE + 1;
@@ -16,6 +16,14 @@ impl MemoryAttr {
         }
     }
 
+    pub fn is_user(&self) -> bool {
+        self.user
+    }
+
+    pub fn is_readonly(&self) -> bool {
+        self.readonly
+    }
+
     pub fn set_user(mut self) -> Self {
         self.user = true;
         self
@@ -31,6 +39,14 @@ impl MemoryAttr {
         self
     }
 
+    // 只修改已有页表项的访问权限，保留是否存在以及写时复制标记
+    pub fn protect(&self, entry : &mut PageEntry) {
+        entry.set_user(self.user);
+        entry.set_writable(!self.readonly && !entry.cow());
+        entry.set_execute(self.excute);
+        entry.update();
+    }
+
     pub fn apply(&self, entry : &mut PageEntry) {
         entry.set_present(true);    // 设置页表项存在
         entry.set_user(self.user);  // 设置用户态访问权限
@@ -1,13 +1,42 @@
 use crate::memory::paging::ActivePageTable;
 use super::attr::MemoryAttr;
-use core::fmt::Debug;
-use alloc::boxed::Box;
+use core::fmt::{ self, Debug, Formatter };
+use alloc::{ boxed::Box, sync::Arc };
+use rcore_fs::vfs::INode;
 
 
-pub trait MemoryHandler : Debug + 'static{
+pub trait MemoryHandler : Debug + Send + Sync + 'static{
     fn box_clone(&self) -> Box<MemoryHandler>;
-    fn map(&self, pt : &mut ActivePageTable, addr : usize, attr : &MemoryAttr); 
+    fn map(&self, pt : &mut ActivePageTable, addr : usize, attr : &MemoryAttr);
     fn unmap(&self, pt : &mut ActivePageTable, addr : usize);
+    // fork 时在原页表中处理 addr 所在的页，返回需要与子进程共享的物理地址
+    fn share(&self, pt : &mut ActivePageTable, addr : usize) -> Option<usize>;
+    // fork 时在子进程的页表中建立 addr 的映射，target 为 share 的返回值
+    fn clone_map(&self, pt : &mut ActivePageTable, addr : usize, target : Option<usize>, attr : &MemoryAttr);
+    // 处理 addr 处的缺页异常，返回是否处理成功
+    fn handle_page_fault(&self, pt : &mut ActivePageTable, addr : usize) -> bool;
+    // 把 addr 所在页的修改写回，调用时该页表必须是当前页表
+    fn sync(&self, _pt : &mut ActivePageTable, _addr : usize) {}
+    // 是否需要调用 sync 写回
+    fn is_shared(&self) -> bool {
+        false
+    }
+    // 是否为只占据地址空间、不能被访问或修改权限的保护页
+    fn is_guard(&self) -> bool {
+        false
+    }
+    // 是否允许通过 mprotect 改为可写
+    fn can_write(&self) -> bool {
+        true
+    }
+    // 共享内存段被挂载到的起始地址，不是共享内存段时返回 None
+    fn shm_addr(&self) -> Option<usize> {
+        None
+    }
+    // 挂载的共享内存段，不是共享内存段时返回 None
+    fn shm_segment(&self) -> Option<Arc<ShmSegment>> {
+        None
+    }
 }
 
 impl Clone for Box<MemoryHandler> {
@@ -34,6 +63,17 @@ impl MemoryHandler for Linear {
         pt.unmap(addr);
     }
 
+    fn share(&self, _pt : &mut ActivePageTable, _addr : usize) -> Option<usize> {
+        None
+    }
+
+    fn clone_map(&self, pt : &mut ActivePageTable, addr : usize, _target : Option<usize>, attr : &MemoryAttr) {
+        self.map(pt, addr, attr);
+    }
+
+    fn handle_page_fault(&self, _pt : &mut ActivePageTable, _addr : usize) -> bool {
+        false
+    }
 }
 
 impl Linear {
@@ -47,7 +87,12 @@ impl Linear {
 #[derive(Debug,Clone)]
 pub struct ByFrame;
 
-use crate::memory::frame_allocator::alloc_frame;
+use crate::memory::frame_allocator::{ alloc_frame, add_frame_ref, frame_ref_count, release_frame };
+use crate::consts::PAGE_SIZE;
+use crate::memory::{ access_user, swap, shm::ShmSegment };
+use riscv::addr::{ Frame, PhysAddr };
+use core::slice;
+
 impl MemoryHandler for ByFrame {
     fn box_clone(&self) -> Box<MemoryHandler> {
         Box::new(self.clone())
@@ -57,10 +102,24 @@ impl MemoryHandler for ByFrame {
         let target = alloc_frame().expect("failed to allocate frame").start_address().as_usize();
         let entry = pt.map(addr, target);
         attr.apply(entry);
+        swap::add_mapping(pt, addr, target);
     }
 
     fn unmap(&self, pt : &mut ActivePageTable, addr : usize) {
-        pt.unmap(addr);
+        unmap_page(pt, addr);
+    }
+
+    fn share(&self, pt : &mut ActivePageTable, addr : usize) -> Option<usize> {
+        swap::swap_in(pt, addr);
+        Some(share_frame(pt, addr))
+    }
+
+    fn clone_map(&self, pt : &mut ActivePageTable, addr : usize, target : Option<usize>, attr : &MemoryAttr) {
+        map_shared(pt, addr, target.expect("ByFrame: page is not mapped"), attr);
+    }
+
+    fn handle_page_fault(&self, pt : &mut ActivePageTable, addr : usize) -> bool {
+        copy_on_write(pt, addr)
     }
 }
 
@@ -68,4 +127,394 @@ impl ByFrame {
     pub fn new() -> Self {
         ByFrame {}
     }
-}
\ No newline at end of file
+}
+
+// 与 ByFrame 相同，但 map 时只填入不存在的页表项，第一次访问触发缺页异常时才分配页帧
+#[derive(Debug,Clone)]
+pub struct ByFrameLazy;
+
+impl MemoryHandler for ByFrameLazy {
+    fn box_clone(&self) -> Box<MemoryHandler> {
+        Box::new(self.clone())
+    }
+
+    fn map(&self, pt: &mut ActivePageTable, addr: usize, attr: &MemoryAttr) {
+        let entry = pt.map(addr, 0);
+        attr.apply(entry);
+        entry.set_present(false);
+        entry.update();
+    }
+
+    fn unmap(&self, pt : &mut ActivePageTable, addr : usize) {
+        unmap_page(pt, addr);
+    }
+
+    fn share(&self, pt : &mut ActivePageTable, addr : usize) -> Option<usize> {
+        swap::swap_in(pt, addr);
+        if pt.get_entry(addr).expect("fail to get entry").present() {
+            Some(share_frame(pt, addr))
+        } else {
+            None
+        }
+    }
+
+    fn clone_map(&self, pt : &mut ActivePageTable, addr : usize, target : Option<usize>, attr : &MemoryAttr) {
+        match target {
+            Some(target) => map_shared(pt, addr, target, attr),
+            None => self.map(pt, addr, attr),
+        }
+    }
+
+    fn handle_page_fault(&self, pt : &mut ActivePageTable, addr : usize) -> bool {
+        match pt.get_entry(addr) {
+            Some(ref entry) if entry.present() => copy_on_write(pt, addr),
+            Some(_) => fill_page(pt, addr, true, |page| page.iter_mut().for_each(|x| *x = 0)),
+            None => false,
+        }
+    }
+}
+
+impl ByFrameLazy {
+    pub fn new() -> Self {
+        ByFrameLazy {}
+    }
+}
+
+// 保护页：只占据地址空间而不建立映射，任何访问都会导致缺页异常
+#[derive(Debug,Clone)]
+pub struct Guard;
+
+impl MemoryHandler for Guard {
+    fn box_clone(&self) -> Box<MemoryHandler> {
+        Box::new(self.clone())
+    }
+
+    fn map(&self, _pt : &mut ActivePageTable, _addr : usize, _attr : &MemoryAttr) {}
+
+    fn unmap(&self, _pt : &mut ActivePageTable, _addr : usize) {}
+
+    fn share(&self, _pt : &mut ActivePageTable, _addr : usize) -> Option<usize> {
+        None
+    }
+
+    fn clone_map(&self, _pt : &mut ActivePageTable, _addr : usize, _target : Option<usize>, _attr : &MemoryAttr) {}
+
+    fn handle_page_fault(&self, _pt : &mut ActivePageTable, _addr : usize) -> bool {
+        false
+    }
+
+    fn is_guard(&self) -> bool {
+        true
+    }
+}
+
+impl Guard {
+    pub fn new() -> Self {
+        Guard {}
+    }
+}
+
+// 文件映射：第一次访问时才分配页帧并从 inode 中读入数据，虚拟地址 vaddr 对应文件中的 offset，两者都按页对齐
+// file_end 及之后的部分不属于文件，填 0。shared 为 true 时与其它进程共享页帧，修改通过 sync 写回文件
+// writable 为文件是否以可写方式打开，不可写的文件的共享映射不能改为可写
+#[derive(Clone)]
+pub struct ByFile {
+    inode : Arc<INode>,
+    vaddr : usize,
+    offset : usize,
+    file_end : usize,
+    shared : bool,
+    writable : bool,
+}
+
+impl Debug for ByFile {
+    fn fmt(&self, f : &mut Formatter) -> fmt::Result {
+        f.debug_struct("ByFile")
+            .field("vaddr", &self.vaddr)
+            .field("offset", &self.offset)
+            .field("file_end", &self.file_end)
+            .field("shared", &self.shared)
+            .field("writable", &self.writable)
+            .finish()
+    }
+}
+
+impl MemoryHandler for ByFile {
+    fn box_clone(&self) -> Box<MemoryHandler> {
+        Box::new(self.clone())
+    }
+
+    fn map(&self, pt: &mut ActivePageTable, addr: usize, attr: &MemoryAttr) {
+        ByFrameLazy.map(pt, addr, attr);
+    }
+
+    fn unmap(&self, pt : &mut ActivePageTable, addr : usize) {
+        ByFrameLazy.unmap(pt, addr);
+    }
+
+    fn share(&self, pt : &mut ActivePageTable, addr : usize) -> Option<usize> {
+        if !self.shared {
+            return ByFrameLazy.share(pt, addr);
+        }
+        // 共享映射需要父子进程使用同一个页帧，还没有读入的页现在读入
+        if !pt.get_entry(addr).expect("fail to get entry").present() {
+            self.handle_page_fault(pt, addr);
+        }
+        let target = pt.get_entry(addr).expect("fail to get entry").target();
+        add_frame_ref(&Frame::of_addr(PhysAddr::new(target)));
+        Some(target)
+    }
+
+    fn clone_map(&self, pt : &mut ActivePageTable, addr : usize, target : Option<usize>, attr : &MemoryAttr) {
+        match target {
+            Some(target) if self.shared => attr.apply(pt.map(addr, target)),
+            _ => ByFrameLazy.clone_map(pt, addr, target, attr),
+        }
+    }
+
+    fn handle_page_fault(&self, pt : &mut ActivePageTable, addr : usize) -> bool {
+        match pt.get_entry(addr) {
+            Some(ref entry) if entry.present() => !self.shared && copy_on_write(pt, addr),
+            // 共享映射的页需要通过 sync 写回文件，不能换出
+            Some(_) => fill_page(pt, addr, !self.shared, |page| self.read_page(addr & !(PAGE_SIZE - 1), page)),
+            None => false,
+        }
+    }
+
+    fn sync(&self, pt : &mut ActivePageTable, addr : usize) {
+        if !self.shared {
+            return;
+        }
+        let page = addr & !(PAGE_SIZE - 1);
+        match pt.get_entry(page) {
+            Some(ref entry) if entry.present() => {},
+            _ => return,
+        }
+        // 只写回文件原有的部分，不改变文件的大小
+        let size = match self.inode.metadata() {
+            Ok(info) => info.size,
+            Err(_) => return,
+        };
+        let offset = self.offset + (page - self.vaddr);
+        let len = (self.file_end.min(page + PAGE_SIZE).saturating_sub(page)).min(size.saturating_sub(offset));
+        if len > 0 {
+            let data = unsafe { slice::from_raw_parts(page as *const u8, len) };
+            access_user(|| self.inode.write_at(offset, data)).expect("failed to write back mmaped page");
+        }
+    }
+
+    fn is_shared(&self) -> bool {
+        self.shared
+    }
+
+    fn can_write(&self) -> bool {
+        !self.shared || self.writable
+    }
+}
+
+impl ByFile {
+    pub fn new(inode : Arc<INode>, vaddr : usize, offset : usize, file_end : usize, shared : bool, writable : bool) -> Self {
+        ByFile { inode, vaddr, offset, file_end, shared, writable }
+    }
+
+    // 读入 page 这一页的内容，超出文件的部分填 0
+    fn read_page(&self, page : usize, buf : &mut [u8]) {
+        let len = self.file_end.min(page + PAGE_SIZE).saturating_sub(page);
+        let read = if len > 0 {
+            self.inode.read_at(self.offset + (page - self.vaddr), &mut buf[..len]).unwrap_or(0)
+        } else {
+            0
+        };
+        buf[read..].iter_mut().for_each(|x| *x = 0);
+    }
+}
+
+// 共享内存段的映射：vaddr 为段被挂载到的起始地址，第一次访问时才取得段中对应的页帧
+// 所有挂载了同一个段的地址空间使用相同的页帧，fork 时子进程也直接共享，不会写时复制
+#[derive(Clone)]
+pub struct ByShm {
+    segment : Arc<ShmSegment>,
+    vaddr : usize,
+    writable : bool,    // 以 SHM_RDONLY 挂载的段不能通过 mprotect 改为可写
+}
+
+impl Debug for ByShm {
+    fn fmt(&self, f : &mut Formatter) -> fmt::Result {
+        f.debug_struct("ByShm")
+            .field("key", &self.segment.key())
+            .field("size", &self.segment.size())
+            .field("vaddr", &self.vaddr)
+            .field("writable", &self.writable)
+            .finish()
+    }
+}
+
+impl MemoryHandler for ByShm {
+    fn box_clone(&self) -> Box<MemoryHandler> {
+        Box::new(self.clone())
+    }
+
+    fn map(&self, pt : &mut ActivePageTable, addr : usize, attr : &MemoryAttr) {
+        ByFrameLazy.map(pt, addr, attr);
+    }
+
+    // 共享内存段中的页不会被换出，只需要减少页帧的引用计数
+    fn unmap(&self, pt : &mut ActivePageTable, addr : usize) {
+        let entry = pt.get_entry(addr).expect("fail to get entry");
+        if entry.present() {
+            let target = entry.target();
+            pt.unmap(addr);
+            release_frame(Frame::of_addr(PhysAddr::new(target)));
+        } else {
+            entry.clear();
+        }
+    }
+
+    fn share(&self, pt : &mut ActivePageTable, addr : usize) -> Option<usize> {
+        let entry = pt.get_entry(addr).expect("fail to get entry");
+        if !entry.present() {
+            return None;
+        }
+        let target = entry.target();
+        add_frame_ref(&Frame::of_addr(PhysAddr::new(target)));
+        Some(target)
+    }
+
+    fn clone_map(&self, pt : &mut ActivePageTable, addr : usize, target : Option<usize>, attr : &MemoryAttr) {
+        match target {
+            Some(target) => attr.apply(pt.map(addr, target)),
+            None => self.map(pt, addr, attr),
+        }
+    }
+
+    fn handle_page_fault(&self, pt : &mut ActivePageTable, addr : usize) -> bool {
+        match pt.get_entry(addr) {
+            Some(ref entry) if !entry.present() => {},
+            _ => return false,
+        }
+        // 先取得页帧再获取页表项，分配时可能会换出其它页
+        let target = self.segment.frame((addr - self.vaddr) / PAGE_SIZE);
+        add_frame_ref(&Frame::of_addr(PhysAddr::new(target)));
+        let entry = pt.get_entry(addr).expect("fail to get entry");
+        entry.set_target(target);
+        entry.set_present(true);
+        entry.update();
+        true
+    }
+
+    fn shm_addr(&self) -> Option<usize> {
+        Some(self.vaddr)
+    }
+
+    fn shm_segment(&self) -> Option<Arc<ShmSegment>> {
+        Some(self.segment.clone())
+    }
+
+    fn can_write(&self) -> bool {
+        self.writable
+    }
+}
+
+impl ByShm {
+    pub fn new(segment : Arc<ShmSegment>, vaddr : usize, writable : bool) -> Self {
+        ByShm { segment, vaddr, writable }
+    }
+}
+
+// 为尚未分配页帧的 addr 分配页帧，并用 fill 初始化这一页的内容，swappable 表示这一页之后能否被换出
+fn fill_page(pt : &mut ActivePageTable, addr : usize, swappable : bool, fill : impl FnOnce(&mut [u8])) -> bool {
+    // 先分配页帧再获取页表项，分配时可能会换出其它页
+    let target = alloc_frame().expect("failed to allocate frame").start_address().as_usize();
+    let entry = pt.get_entry(addr).expect("fail to get entry");
+    let writable = entry.writable();
+    entry.set_target(target);
+    entry.set_present(true);
+    entry.set_writable(true);   // 初始化时需要写权限，之后再恢复
+    entry.update();
+    let page = addr & !(PAGE_SIZE - 1);
+    access_user(|| fill(unsafe { slice::from_raw_parts_mut(page as *mut u8, PAGE_SIZE) }));
+    entry.set_writable(writable);
+    entry.update();
+    if swappable {
+        swap::add_mapping(pt, addr, target);
+    }
+    true
+}
+
+// 取消 addr 的映射，并释放其页帧（与其它页表共享时只减少引用计数）
+fn unmap_frame(pt : &mut ActivePageTable, addr : usize) {
+    let target = pt.get_entry(addr).expect("fail to get entry").target();
+    pt.unmap(addr);
+    swap::remove_mapping(pt, addr, target);
+    release_frame(Frame::of_addr(PhysAddr::new(target)));
+}
+
+// 取消 addr 的映射，页可能还没有分配页帧，也可能已经被换出
+fn unmap_page(pt : &mut ActivePageTable, addr : usize) {
+    let entry = pt.get_entry(addr).expect("fail to get entry");
+    if entry.present() {
+        unmap_frame(pt, addr);
+    } else {
+        if entry.swapped() {
+            swap::free_slot(entry.target() / PAGE_SIZE);
+        }
+        entry.clear();
+    }
+}
+
+// fork 时把 addr 所在的页与子进程共享，返回其物理地址
+fn share_frame(pt : &mut ActivePageTable, addr : usize) -> usize {
+    let entry = pt.get_entry(addr).expect("fail to get entry");
+    // 改为只读并打上写时复制标记，等到第一次写入时再复制
+    // 只读的页也要打上标记，否则之后通过 mprotect 改为可写时会直接写入仍在共享的页帧
+    entry.set_writable(false);
+    entry.set_cow(true);
+    entry.update();
+    let target = entry.target();
+    add_frame_ref(&Frame::of_addr(PhysAddr::new(target)));
+    target
+}
+
+// 在子进程中映射共享的页
+fn map_shared(pt : &mut ActivePageTable, addr : usize, target : usize, attr : &MemoryAttr) {
+    let entry = pt.map(addr, target);
+    attr.apply(entry);
+    entry.set_writable(false);
+    entry.set_cow(true);
+    swap::add_mapping(pt, addr, target);
+}
+
+// 处理对写时复制页的写入，不是写时复制页时返回 false
+fn copy_on_write(pt : &mut ActivePageTable, addr : usize) -> bool {
+    let entry = match pt.get_entry(addr) {
+        Some(entry) => entry,
+        None => return false,
+    };
+    if !entry.cow() {
+        return false;
+    }
+    let frame = Frame::of_addr(PhysAddr::new(entry.target()));
+    if frame_ref_count(&frame) == 1 {
+        // 其它共享者都已经释放了这一页，直接恢复写权限
+        entry.set_cow(false);
+        entry.set_writable(true);
+        entry.update();
+        return true;
+    }
+    // 先通过原来的只读映射把数据读出来，换上新的页帧后再写回去
+    let page = addr & !(PAGE_SIZE - 1);
+    let mut data = [0u8; PAGE_SIZE];
+    access_user(|| data.copy_from_slice(unsafe { slice::from_raw_parts(page as *const u8, PAGE_SIZE) }));
+    let target = alloc_frame().expect("failed to allocate frame").start_address().as_usize();
+    // 分配页帧时可能换出了其它页，需要重新获取页表项
+    let entry = pt.get_entry(addr).expect("fail to get entry");
+    entry.set_target(target);
+    entry.set_cow(false);
+    entry.set_writable(true);
+    entry.update();
+    swap::remove_mapping(pt, addr, frame.start_address().as_usize());
+    swap::add_mapping(pt, addr, target);
+    release_frame(frame);
+    access_user(|| unsafe { slice::from_raw_parts_mut(page as *mut u8, PAGE_SIZE) }.copy_from_slice(&data));
+    true
+}
@@ -3,8 +3,10 @@ pub mod handler;
 pub mod attr;
 
 use self::{area::MemoryArea, handler::MemoryHandler, attr::MemoryAttr};
-use crate::memory::paging::InactivePageTable;
-use alloc::{boxed::Box, vec::Vec,};
+use crate::memory::paging::{ InactivePageTable, PageRange, active_table };
+use crate::consts::PAGE_SIZE;
+use alloc::{boxed::Box, sync::Arc, vec::Vec,};
+use crate::memory::shm::ShmSegment;
 
 pub struct MemorySet{
     areas : Vec<MemoryArea>,
@@ -46,13 +48,170 @@ impl MemorySet {
         self.areas.push(area);
     }
 
-    fn test_free_area(&self, start_addr : usize, end_addr : usize) -> bool {
+    pub fn test_free_area(&self, start_addr : usize, end_addr : usize) -> bool {
         self.areas
             .iter()
             .find(|area| area.is_overlap_with(start_addr, end_addr))
             .is_none()
     }
 
+    // 复制出一个新的地址空间，用户页以写时复制的方式共享
+    pub fn clone(&mut self) -> MemorySet {
+        let mut new_set = MemorySet::new_kern();
+        for area in self.areas.iter() {
+            let targets = self.page_table.edit(|pt| area.share(pt));
+            new_set.page_table.edit(|pt| area.clone_map(pt, &targets));
+            new_set.areas.push(area.clone());
+        }
+        new_set
+    }
+
+    // 在 [start, end) 中从低地址开始寻找一段长度为 len 的空闲地址，len 需要按页对齐
+    pub fn find_free_area(&self, start : usize, end : usize, len : usize) -> Option<usize> {
+        let mut addr = start;
+        while addr.checked_add(len)? <= end {
+            match self.areas.iter().find(|area| area.is_overlap_with(addr, addr + len)) {
+                Some(area) => addr = (area.end() + PAGE_SIZE - 1) & !(PAGE_SIZE - 1),
+                None => return Some(addr),
+            }
+        }
+        None
+    }
+
+    // 把与 [start, end) 相交的区域在边界处拆开，start 和 end 需要按页对齐
+    fn split_at(&mut self, start : usize, end : usize) {
+        let mut new_areas = Vec::new();
+        for area in self.areas.iter_mut() {
+            for &addr in [start, end].iter() {
+                if area.start() < addr && addr < area.end() {
+                    let right = area.split(addr);
+                    new_areas.push(right);
+                }
+            }
+        }
+        // 拆出的右半部分可能还需要在 end 处再拆一次
+        for mut area in new_areas {
+            if area.start() < end && end < area.end() {
+                let right = area.split(end);
+                self.areas.push(right);
+            }
+            self.areas.push(area);
+        }
+    }
+
+    // 把 [start, end) 中共享文件映射的修改写回文件
+    pub fn sync(&self, start : usize, end : usize) {
+        let areas: Vec<&MemoryArea> = self.areas
+            .iter()
+            .filter(|area| area.is_shared() && area.is_overlap_with(start, end))
+            .collect();
+        if areas.is_empty() {
+            return;
+        }
+        // 需要通过用户地址读取页的内容，因此切换到这个页表
+        unsafe {
+            self.page_table.with(|| {
+                let mut pt = active_table();
+                for area in areas {
+                    area.sync(&mut pt, start, end);
+                }
+            });
+        }
+    }
+
+    // 从地址空间中移除 [start, end)，与之部分相交的区域会被截断或拆分
+    pub fn remove(&mut self, start : usize, end : usize) {
+        self.sync(start, end);
+        self.split_at(start, end);
+        let page_table = &mut self.page_table;
+        self.areas.retain(|area| {
+            if start <= area.start() && area.end() <= end {
+                page_table.edit(|pt| area.unmap(pt));
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    // 取消挂载在 addr 处的共享内存段，包括被拆分后剩下的所有部分。返回这个段，没有挂载在 addr 处的段时返回 None
+    pub fn detach(&mut self, addr : usize) -> Option<Arc<ShmSegment>> {
+        let page_table = &mut self.page_table;
+        let mut found = None;
+        self.areas.retain(|area| {
+            if area.shm_addr() == Some(addr) {
+                page_table.edit(|pt| area.unmap(pt));
+                found = area.shm_segment();
+                false
+            } else {
+                true
+            }
+        });
+        found
+    }
+
+    // 共享内存段 segment 是否还挂载在这个地址空间中
+    pub fn is_attached(&self, segment : &Arc<ShmSegment>) -> bool {
+        self.areas
+            .iter()
+            .any(|area| area.shm_segment().map_or(false, |other| Arc::ptr_eq(&other, segment)))
+    }
+
+    // 修改 [start, end) 的访问权限，范围中有未映射的页或保护页时返回 false
+    pub fn protect(&mut self, start : usize, end : usize, attr : MemoryAttr) -> bool {
+        let covered = PageRange::new(start, end).all(|page| {
+            self.areas.iter().any(|area| area.contains(page) && !area.is_guard())
+        });
+        if !covered {
+            return false;
+        }
+        self.split_at(start, end);
+        let page_table = &mut self.page_table;
+        for area in self.areas.iter_mut() {
+            if start <= area.start() && area.end() <= end {
+                page_table.edit(|pt| area.protect(pt, attr.clone()));
+            }
+        }
+        true
+    }
+
+    // 与 [start, end) 相交的区域是否都允许改为可写
+    pub fn can_write(&self, start : usize, end : usize) -> bool {
+        self.areas
+            .iter()
+            .filter(|area| area.is_overlap_with(start, end))
+            .all(|area| area.can_write())
+    }
+
+    // [start, end) 中的每一页是否都属于用户可访问的区域，write 为 true 时还要求可写
+    pub fn check_user_range(&self, start : usize, end : usize, write : bool) -> bool {
+        if start >= end {
+            return true;
+        }
+        PageRange::new(start, end).all(|page| {
+            self.areas.iter().any(|area| {
+                area.contains(page) && area.attr().is_user() && !(write && area.attr().is_readonly())
+            })
+        })
+    }
+
+    pub fn handle_page_fault(&mut self, addr : usize) -> bool {
+        match self.areas.iter().find(|area| area.contains(addr)) {
+            Some(area) => self.page_table.edit(|pt| area.handle_page_fault(pt, addr)),
+            None => false,
+        }
+    }
+
+    // 写回共享文件映射后移除所有区域，释放其中的页帧
+    pub fn clear(&mut self) {
+        self.sync(0, usize::max_value());
+        let page_table = &mut self.page_table;
+        for area in self.areas.iter() {
+            page_table.edit(|pt| area.unmap(pt));
+        }
+        self.areas.clear();
+    }
+
     pub unsafe fn activate(&self) {
         self.page_table.activate();
     }
@@ -64,4 +223,11 @@ impl MemorySet {
     pub fn token(&self) -> usize {
         self.page_table.token()
     }
-}
\ No newline at end of file
+}
+
+// 页表本身在 InactivePageTable 被释放时回收
+impl Drop for MemorySet {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
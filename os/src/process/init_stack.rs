@@ -0,0 +1,99 @@
+use alloc::{ string::String, vec::Vec };
+use core::mem::size_of;
+
+// 辅助向量的类型
+pub const AT_NULL: usize = 0;
+pub const AT_PHDR: usize = 3;
+pub const AT_PHENT: usize = 4;
+pub const AT_PHNUM: usize = 5;
+pub const AT_PAGESZ: usize = 6;
+pub const AT_ENTRY: usize = 9;
+
+// 新进程的初始用户栈，布局与 System V ABI 相同（从低地址到高地址）：
+// argc, argv[0..argc], NULL, envp[..], NULL, auxv[..], AT_NULL, 0, 字符串
+pub struct InitStack {
+    pub args: Vec<String>,
+    pub envs: Vec<String>,
+    pub auxv: Vec<(usize, usize)>,
+}
+
+// 初始栈的内容先在内核中构造好，再拷贝到新的地址空间中
+pub struct InitStackData {
+    pub data: Vec<u8>,  // 从 sp 到栈顶的内容
+    pub sp: usize,
+    pub argc: usize,
+    pub argv: usize,
+    pub envp: usize,
+}
+
+impl InitStack {
+    pub fn size(&self) -> usize {
+        let strings: usize = self.args.iter().chain(self.envs.iter()).map(|s| s.len() + 1).sum();
+        let words = 1 + (self.args.len() + 1) + (self.envs.len() + 1) + (self.auxv.len() + 1) * 2;
+        align_up(strings, 16) + align_up(words * size_of::<usize>(), 16)
+    }
+
+    pub fn build(&self, stack_top: usize) -> InitStackData {
+        let size = self.size();
+        let sp = stack_top - size;
+        let mut data = Vec::new();
+        data.resize(size, 0u8);
+        let mut writer = Writer { base: sp, data: &mut data, pos: size };
+
+        // 字符串放在栈顶
+        let envs: Vec<usize> = self.envs.iter().map(|s| writer.push_str(s)).collect();
+        let args: Vec<usize> = self.args.iter().map(|s| writer.push_str(s)).collect();
+
+        // 其余内容从 sp 开始依次向上存放
+        let mut pos = 0;
+        let mut push_word = |data: &mut Vec<u8>, value: usize| {
+            data[pos..pos + size_of::<usize>()].copy_from_slice(&value.to_ne_bytes());
+            pos += size_of::<usize>();
+        };
+        push_word(&mut data, args.len());
+        let argv = sp + size_of::<usize>();
+        for &arg in args.iter() {
+            push_word(&mut data, arg);
+        }
+        push_word(&mut data, 0);
+        let envp = argv + (args.len() + 1) * size_of::<usize>();
+        for &env in envs.iter() {
+            push_word(&mut data, env);
+        }
+        push_word(&mut data, 0);
+        for &(key, value) in self.auxv.iter() {
+            push_word(&mut data, key);
+            push_word(&mut data, value);
+        }
+        push_word(&mut data, AT_NULL);
+        push_word(&mut data, 0);
+
+        InitStackData {
+            data,
+            sp,
+            argc: args.len(),
+            argv,
+            envp,
+        }
+    }
+}
+
+struct Writer<'a> {
+    base: usize,
+    data: &'a mut Vec<u8>,
+    pos: usize,
+}
+
+impl Writer<'_> {
+    // 从高地址向低地址写入以 '\0' 结尾的字符串，返回其在用户地址空间中的地址
+    fn push_str(&mut self, s: &str) -> usize {
+        self.pos -= s.len() + 1;
+        self.data[self.pos..self.pos + s.len()].copy_from_slice(s.as_bytes());
+        self.data[self.pos + s.len()] = 0;
+        self.base + self.pos
+    }
+}
+
+fn align_up(value: usize, align: usize) -> usize {
+    (value + align - 1) & !(align - 1)
+}
@@ -1,29 +1,82 @@
-mod structs;
+pub mod structs;
+mod init_stack;
 mod scheduler;
 mod processor;
 mod thread_pool;
 
-use structs::Thread;
-use alloc::boxed::Box;
+use structs::{ Thread, Process, UserImage, UserHeap, KSTACK_CACHE };
+use alloc::{ boxed::Box, sync::Arc, string::String, vec::Vec };
+use core::mem;
+use spin::Mutex;
+use lazy_static::*;
 use processor::Processor;
 use thread_pool::ThreadPool;
-use self::scheduler::Scheduler;
 use crate::fs::ROOT_INODE;
 use crate::fs::INodeExt;
+use rcore_fs::vfs::FsError;
+use crate::context::TrapFrame;
+use crate::memory::frame_allocator::free_frames;
+use crate::interrupt::{ disable_and_store, restore };
 
 pub type Tid = usize;
+pub type Pid = usize;
 pub type ExitCode = usize;
 
 static CPU: Processor = Processor::new();
 
+lazy_static! {
+    // 第一个用户进程作为 init 进程，负责收养孤儿进程
+    static ref INIT_PROCESS: Mutex<Option<Arc<Process>>> = Mutex::new(None);
+}
+
 pub fn tick() {
     CPU.tick();
 }
 
+// 结束当前线程，进程中最后一个线程退出时进程退出
 pub fn exit(code: usize) {
+    // CPU.exit 不会返回，需要先释放这里持有的 Arc
+    if let Some(proc) = current_process() {
+        // 进程被结束时，最后一个退出的线程以结束进程时的退出码退出进程
+        if proc.exit_thread(current_tid(), code) {
+            exit_process(&proc, proc.killed().unwrap_or(code));
+        }
+    }
     CPU.exit(code);
 }
 
+// 结束当前进程的所有线程。睡眠的线程被唤醒，其余线程都在回到用户态之前退出，
+// 最后一个线程退出时才释放地址空间，此前其它线程可能还在内核中访问它
+pub fn kill(code: usize) {
+    if let Some(proc) = current_process() {
+        proc.kill(code);
+        let tid = current_tid();
+        for other in proc.threads().into_iter().filter(|&other| other != tid) {
+            wake_up(other);
+        }
+    }
+    exit(code);
+}
+
+// 当前进程已经被结束时结束当前线程，在回到用户态之前调用
+pub fn exit_if_killed() {
+    if let Some(code) = current_process().and_then(|proc| proc.killed()) {
+        exit(code);
+    }
+}
+
+fn exit_process(proc: &Arc<Process>, code: usize) {
+    let init = {
+        let mut init = INIT_PROCESS.lock();
+        // init 进程退出之后不再有进程收养孤儿进程
+        if init.as_ref().map_or(false, |init| init.pid == proc.pid) {
+            *init = None;
+        }
+        init.clone()
+    };
+    proc.exit(code, init);
+}
+
 pub fn kmain() {
     CPU.run();
 }
@@ -32,32 +85,194 @@ pub fn yield_now() {
     CPU.yield_now();
 }
 
+// 睡眠 ticks 个时钟周期。设置定时器之后、睡眠之前不能被中断，否则定时器可能在睡眠之前到期，唤醒被丢失
+pub fn sleep(ticks: usize) {
+    let tid = current_tid();
+    let flags = disable_and_store();
+    crate::timer::add_timer(crate::clock::get_tick().saturating_add(ticks), crate::timer::TimerEvent::Wakeup(tid));
+    yield_now();
+    restore(flags);
+}
+
 pub fn wake_up(tid : Tid) {
     CPU.wake_up(tid);
 }
 
+// 设置当前线程的优先级
+pub fn set_priority(priority: usize) {
+    CPU.set_priority(priority);
+}
+
 pub fn current_tid() -> usize {
     CPU.current_tid()
 }
 
-pub fn excute(name : &str) {
+pub fn current_process() -> Option<Arc<Process>> {
+    CPU.current_thread().and_then(|thread| thread.proc.clone())
+}
+
+// 当前线程所属的进程是否已经被结束。阻塞等待的循环每次被唤醒后都要检查，被结束时放弃等待，尽快回到用户态退出
+pub fn killed() -> bool {
+    current_process().map_or(false, |proc| proc.killed().is_some())
+}
+
+// 把用户线程加入线程池，并记录到它所属的进程中
+fn add_user_thread(thread: Box<Thread>) -> Tid {
+    let proc = thread.proc.clone().expect("not a user thread");
+    let tid = CPU.add_thread(thread);
+    proc.add_thread(tid);
+    tid
+}
+
+pub fn fork(tf: &TrapFrame) -> Pid {
+    let thread = CPU.current_thread().expect("no thread is running").fork(tf);
+    let pid = thread.proc.as_ref().unwrap().pid;
+    add_user_thread(thread);
+    pid
+}
+
+// 在当前进程中创建一个从 entry 开始执行的线程，返回其 tid
+pub fn thread_create(entry: usize, ustack_top: usize, arg: usize) -> Tid {
+    let thread = CPU.current_thread().expect("no thread is running").new_thread(entry, ustack_top, arg);
+    add_user_thread(thread)
+}
+
+// 在当前进程中复制当前线程，新线程在 ustack_top 上从系统调用返回 0，返回其 tid
+pub fn clone_thread(tf: &TrapFrame, ustack_top: usize) -> Tid {
+    let thread = CPU.current_thread().expect("no thread is running").clone_thread(tf, ustack_top);
+    add_user_thread(thread)
+}
+
+// 等待当前进程中的线程 tid 退出，返回其退出码
+pub fn thread_join(tid: Tid) -> Option<ExitCode> {
+    current_process().expect("kernel thread can not join").join(tid)
+}
+
+// 等待子进程退出，返回子进程的 pid 和退出码
+pub fn wait(pid: Option<Pid>) -> Option<(Pid, ExitCode)> {
+    current_process().expect("kernel thread has no child").wait(pid)
+}
+
+// 处理当前用户进程的缺页异常，返回是否处理成功
+pub fn handle_page_fault(addr: usize) -> bool {
+    match CPU.current_thread().and_then(|thread| thread.proc.as_ref()) {
+        Some(proc) => proc.vm.lock().handle_page_fault(addr),
+        None => false,
+    }
+}
+
+#[derive(Debug)]
+pub enum ExecError {
+    Fs(FsError),    // 找不到或读取文件失败
+    NotExecutable,  // 不是可执行的 ELF 文件
+    MultiThreaded,  // 进程中还有其它线程
+}
+
+// 创建一个新进程执行 name，只用于启动 init 进程
+pub fn excute(name : &str, args: Vec<String>) -> Result<Pid, ExecError> {
     println!("excutint program: {}", name);
-    let data = ROOT_INODE
-        .lookup(name)
-        .unwrap()
-        .read_as_vec()
-        .unwrap();
-    let thread = unsafe{ Thread::new_user(data.as_slice()) };
-    CPU.add_thread(thread);
+    let inode = ROOT_INODE.lookup(name).map_err(ExecError::Fs)?;
+    let data = inode.read_as_vec().map_err(ExecError::Fs)?;
+    let parent = current_process();
+    let thread = unsafe{ Thread::new_user(&inode, data.as_slice(), args, Vec::new(), parent.as_ref()) }
+        .ok_or(ExecError::NotExecutable)?;
+    let proc = thread.proc.clone().unwrap();
+    if parent.is_none() {
+        *INIT_PROCESS.lock() = Some(proc.clone());
+    }
+    add_user_thread(thread);
+    Ok(proc.pid)
 }
 
-pub fn init() {
+// 用 path 指向的程序替换当前进程的地址空间，并修改 tf 使其返回后从新程序的入口开始执行
+// 成功时返回 argc，作为新程序中 a0 的值
+// 其它线程可能正在使用旧的地址空间，因此只允许单线程的进程调用
+pub fn execve(path: &str, args: Vec<String>, envs: Vec<String>, tf: &mut TrapFrame) -> Result<usize, ExecError> {
+    let proc = current_process().expect("kernel thread can not execve");
+    if proc.threads().len() > 1 {
+        return Err(ExecError::MultiThreaded);
+    }
+    let inode = ROOT_INODE.lookup(path).map_err(ExecError::Fs)?;
+    let data = inode.read_as_vec().map_err(ExecError::Fs)?;
+    let image = UserImage::load(&inode, data.as_slice(), args, envs).ok_or(ExecError::NotExecutable)?;
+    let old_vm = {
+        let mut vm = proc.vm.lock();
+        let old_vm = mem::replace(&mut *vm, image.vm);
+        unsafe { vm.activate(); }
+        old_vm
+    };
+    // 切换到新页表之后才能释放旧的地址空间
+    drop(old_vm);
+    *proc.heap.lock() = UserHeap::new(image.heap_start);
+    // 旧程序创建的互斥锁和信号量对新程序没有意义
+    proc.mutexes.lock().clear();
+    proc.semaphores.lock().clear();
+
+    // 清空寄存器，sstatus 保持不变，sret 后仍回到用户态
+    tf.x = [0; 32];
+    tf.x[2] = image.sp;
+    tf.x[11] = image.argv;
+    tf.x[12] = image.envp;
+    tf.sepc = image.entry;
+    Ok(image.argc)
+}
+
+pub fn init(bootargs: &str) {
     println!("+------ now to initialize process ------+");
-    let scheduler = Scheduler::new(1);
+    crate::memory::slab::register(&KSTACK_CACHE);
+    let scheduler = scheduler::from_bootargs(bootargs, 1);
     let thread_pool = ThreadPool::new(100, scheduler);
     println!("+------ now to initialize processor ------+");
     CPU.init(Thread::new_idle(), Box::new(thread_pool));
-    excute("rust/shell");
+    // 启动参数中有 memtest=N 时先进行 N 轮内存回收的测试，结束后再启动 shell
+    let memtest = bootargs
+        .split_whitespace()
+        .find(|arg| arg.starts_with("memtest="))
+        .and_then(|arg| arg["memtest=".len()..].parse::<usize>().ok());
+    match memtest {
+        Some(times) => {
+            CPU.add_thread(Thread::new_kernel(memtest_thread, times));
+        },
+        None => start_shell(),
+    }
+}
+
+fn start_shell() {
+    let shell = "rust/shell";
+    let mut args = Vec::new();
+    args.push(String::from(shell));
+    excute(shell, args).expect("failed to start shell");
+}
+
+// 创建一个没有父进程的进程执行 name，等待它退出后释放
+fn run_to_exit(name: &str) {
+    let inode = ROOT_INODE.lookup(name).expect("memtest: program not found");
+    let data = inode.read_as_vec().expect("memtest: failed to read program");
+    let mut args = Vec::new();
+    args.push(String::from(name));
+    let thread = unsafe { Thread::new_user(&inode, data.as_slice(), args, Vec::new(), None) }
+        .expect("memtest: not an executable");
+    let proc = thread.proc.clone().unwrap();
+    add_user_thread(thread);
+    while proc.exit_code().is_none() {
+        sleep(1);
+    }
+}
+
+// 反复运行同一个用户程序，检查每个进程退出并被回收后空闲页帧数保持不变
+extern "C" fn memtest_thread(times: usize) -> ! {
+    let name = "rust/hello";
+    // 第一次运行会分配一些之后一直保留的页帧，例如内核页表中临时映射用的二级页表
+    run_to_exit(name);
+    let free = free_frames();
+    for i in 0..times {
+        run_to_exit(name);
+        assert_eq!(free_frames(), free, "memtest: frames leaked after {} runs", i + 1);
+    }
+    println!("memtest passed: {} runs, {} free frames", times, free);
+    crate::memory::print_stats();
+    start_shell();
+    CPU.exit(0)
 }
 
 #[no_mangle]
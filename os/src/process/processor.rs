@@ -41,8 +41,8 @@ impl Processor {
             .expect("Processor is not initialized")
     }
 
-    pub fn add_thread(&self, thread: Box<Thread>) {
-        self.inner().pool.add(thread);
+    pub fn add_thread(&self, thread: Box<Thread>) -> Tid {
+        self.inner().pool.add(thread)
     }
 
     pub fn run(&self) -> !{
@@ -125,6 +125,12 @@ impl Processor {
         }
     }
 
+    pub fn set_priority(&self, priority: usize) {
+        let inner = self.inner();
+        let tid = inner.current.as_ref().unwrap().0;
+        inner.pool.set_priority(tid, priority);
+    }
+
     pub fn wake_up(&self, tid: Tid) {
         let inner = self.inner();
         inner.pool.wakeup(tid);
@@ -133,6 +139,10 @@ impl Processor {
     pub fn current_tid(&self) -> usize {
         self.inner().current.as_mut().unwrap().0 as usize
     }
+
+    pub fn current_thread(&self) -> Option<&Thread> {
+        self.inner().current.as_ref().map(|(_, thread)| &**thread)
+    }
 }
 
 use crate::interrupt::{ disable_and_store, enable_and_wfi };
\ No newline at end of file
@@ -0,0 +1,108 @@
+use crate::process::Tid;
+use super::Scheduler;
+use alloc::{ vec::Vec, collections::VecDeque };
+
+// 多级反馈队列：第 i 级队列的时间片为 max_time << i
+// 用完时间片的线程降一级，主动让出 CPU 的线程保持原来的级别
+// 每隔 BOOST_INTERVAL 个时钟周期把所有线程提回最高级，防止饥饿
+const LEVELS: usize = 4;
+const BOOST_INTERVAL: usize = 100;
+
+#[derive(Default, Clone)]
+struct MLFQInfo {
+    level: usize,
+    time: usize,
+}
+
+pub struct MLFQScheduler {
+    queues: Vec<VecDeque<Tid>>,
+    threads: Vec<MLFQInfo>,
+    max_time: usize,
+    current: Option<Tid>,
+    ticks: usize,
+}
+
+impl MLFQScheduler {
+    pub fn new(max_time_slice: usize) -> Self {
+        let mut queues = Vec::new();
+        queues.resize_with(LEVELS, Default::default);
+        MLFQScheduler {
+            queues,
+            threads: Vec::new(),
+            max_time: max_time_slice,
+            current: None,
+            ticks: 0,
+        }
+    }
+
+    fn info(&mut self, tid: Tid) -> &mut MLFQInfo {
+        if tid >= self.threads.len() {
+            self.threads.resize_with(tid + 1, Default::default);
+        }
+        &mut self.threads[tid]
+    }
+
+    fn boost(&mut self) {
+        for level in 1..LEVELS {
+            while let Some(tid) = self.queues[level].pop_front() {
+                self.queues[0].push_back(tid);
+            }
+        }
+        for info in self.threads.iter_mut() {
+            info.level = 0;
+        }
+    }
+}
+
+impl Scheduler for MLFQScheduler {
+    fn push(&mut self, tid: Tid) {
+        let max_time = self.max_time;
+        let info = self.info(tid);
+        if info.time == 0 {
+            info.time = max_time << info.level;
+        }
+        let level = info.level;
+        self.queues[level].push_back(tid);
+    }
+
+    fn pop(&mut self) -> Option<Tid> {
+        self.current = self.queues.iter_mut().find_map(|queue| queue.pop_front());
+        self.current
+    }
+
+    fn tick(&mut self) -> bool {
+        self.ticks += 1;
+        if self.ticks % BOOST_INTERVAL == 0 {
+            self.boost();
+        }
+        match self.current {
+            Some(tid) => {
+                let info = &mut self.threads[tid];
+                if info.time > 0 {
+                    info.time -= 1;
+                }
+                if info.time == 0 {
+                    // 用完了整个时间片，说明是计算密集型线程，降低一级
+                    info.level = (info.level + 1).min(LEVELS - 1);
+                    true
+                } else {
+                    false
+                }
+            },
+            None => true,
+        }
+    }
+
+    fn exit(&mut self, tid: Tid) {
+        if self.current == Some(tid) {
+            self.current = None;
+        }
+        if tid < self.threads.len() {
+            self.threads[tid] = MLFQInfo::default();
+        }
+    }
+
+    // 多级反馈队列根据线程的行为调整级别，忽略设置的优先级
+    fn set_priority(&mut self, _tid: Tid, _priority: usize) {
+    }
+}
@@ -0,0 +1,46 @@
+use crate::process::Tid;
+use alloc::boxed::Box;
+
+mod rr;
+mod stride;
+mod mlfq;
+
+pub use self::rr::RRScheduler;
+pub use self::stride::StrideScheduler;
+pub use self::mlfq::MLFQScheduler;
+
+// 调度算法的接口，ThreadPool 通过它选择下一个运行的线程
+pub trait Scheduler {
+    // 将线程加入就绪队列
+    fn push(&mut self, tid: Tid);
+    // 取出下一个要运行的线程
+    fn pop(&mut self) -> Option<Tid>;
+    // 时钟中断时调用，返回当前线程是否需要让出 CPU
+    fn tick(&mut self) -> bool;
+    // 线程退出
+    fn exit(&mut self, tid: Tid);
+    // 设置线程的优先级，不支持优先级的算法可以忽略
+    fn set_priority(&mut self, tid: Tid, priority: usize);
+}
+
+// 根据启动参数中的 sched=rr|stride|mlfq 选择调度算法，默认为 RR
+pub fn from_bootargs(bootargs: &str, max_time_slice: usize) -> Box<Scheduler> {
+    let name = bootargs
+        .split_whitespace()
+        .find(|arg| arg.starts_with("sched="))
+        .map_or("rr", |arg| &arg["sched=".len()..]);
+    match name {
+        "stride" => {
+            println!("scheduler: stride");
+            Box::new(StrideScheduler::new(max_time_slice))
+        },
+        "mlfq" => {
+            println!("scheduler: mlfq");
+            Box::new(MLFQScheduler::new(max_time_slice))
+        },
+        _ => {
+            println!("scheduler: round robin");
+            Box::new(RRScheduler::new(max_time_slice))
+        },
+    }
+}
@@ -0,0 +1,37 @@
+use crate::process::Tid;
+use super::Scheduler;
+use RoundRobinScheduler::RRScheduler as RRInner;
+
+pub struct RRScheduler {
+    scheduler: RRInner,
+}
+
+impl RRScheduler {
+    pub fn new(max_time_slice: usize) -> RRScheduler {
+        let s = RRScheduler {
+            scheduler: RRInner::new(max_time_slice),
+        };
+        s
+    }
+}
+
+impl Scheduler for RRScheduler {
+    fn push(&mut self, tid: Tid) {
+        self.scheduler.push(tid);
+    }
+
+    fn pop(&mut self) -> Option<Tid> {
+        self.scheduler.pop()
+    }
+
+    fn tick(&mut self) -> bool {
+        self.scheduler.tick()
+    }
+
+    fn exit(&mut self, tid: Tid) {
+        self.scheduler.exit(tid);
+    }
+
+    fn set_priority(&mut self, _tid: Tid, _priority: usize) {
+    }
+}
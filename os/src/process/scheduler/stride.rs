@@ -0,0 +1,122 @@
+use crate::process::Tid;
+use super::Scheduler;
+use alloc::vec::Vec;
+
+// 步长调度：每次选择行程值最小的线程运行，运行后行程值增加 BIG_STRIDE / 优先级
+// 优先级（票数）越高，步长越小，得到的 CPU 时间越多
+const BIG_STRIDE: usize = 1 << 20;
+const DEFAULT_PRIORITY: usize = 16;
+
+#[derive(Clone)]
+struct StrideInfo {
+    ready: bool,
+    priority: usize,
+    pass: usize,
+    time: usize,
+}
+
+impl Default for StrideInfo {
+    fn default() -> Self {
+        StrideInfo {
+            ready: false,
+            priority: DEFAULT_PRIORITY,
+            pass: 0,
+            time: 0,
+        }
+    }
+}
+
+pub struct StrideScheduler {
+    threads: Vec<StrideInfo>,
+    max_time: usize,
+    current: Option<Tid>,
+}
+
+impl StrideScheduler {
+    pub fn new(max_time_slice: usize) -> Self {
+        StrideScheduler {
+            threads: Vec::new(),
+            max_time: max_time_slice,
+            current: None,
+        }
+    }
+
+    fn info(&mut self, tid: Tid) -> &mut StrideInfo {
+        if tid >= self.threads.len() {
+            self.threads.resize_with(tid + 1, Default::default);
+        }
+        &mut self.threads[tid]
+    }
+}
+
+impl Scheduler for StrideScheduler {
+    fn push(&mut self, tid: Tid) {
+        // 新加入的线程从当前最小的行程值开始，避免长时间独占 CPU
+        let min_pass = self.threads
+            .iter()
+            .filter(|info| info.ready)
+            .map(|info| info.pass)
+            .fold(None, |min, pass| match min {
+                Some(min) if (pass.wrapping_sub(min) as isize) >= 0 => Some(min),
+                _ => Some(pass),
+            });
+        let max_time = self.max_time;
+        let info = self.info(tid);
+        if let Some(min_pass) = min_pass {
+            if (info.pass.wrapping_sub(min_pass) as isize) < 0 {
+                info.pass = min_pass;
+            }
+        }
+        if info.time == 0 {
+            info.time = max_time;
+        }
+        info.ready = true;
+    }
+
+    fn pop(&mut self) -> Option<Tid> {
+        // 行程值可能溢出回绕，用差值的符号比较大小
+        let mut ret: Option<Tid> = None;
+        for (tid, info) in self.threads.iter().enumerate() {
+            if !info.ready {
+                continue;
+            }
+            ret = match ret {
+                Some(best) if (info.pass.wrapping_sub(self.threads[best].pass) as isize) >= 0 => Some(best),
+                _ => Some(tid),
+            };
+        }
+        if let Some(tid) = ret {
+            let info = &mut self.threads[tid];
+            info.ready = false;
+            info.pass = info.pass.wrapping_add(BIG_STRIDE / info.priority);
+        }
+        self.current = ret;
+        ret
+    }
+
+    fn tick(&mut self) -> bool {
+        match self.current {
+            Some(tid) => {
+                let info = &mut self.threads[tid];
+                if info.time > 0 {
+                    info.time -= 1;
+                }
+                info.time == 0
+            },
+            None => true,
+        }
+    }
+
+    fn exit(&mut self, tid: Tid) {
+        if self.current == Some(tid) {
+            self.current = None;
+        }
+        if tid < self.threads.len() {
+            self.threads[tid] = StrideInfo::default();
+        }
+    }
+
+    fn set_priority(&mut self, tid: Tid, priority: usize) {
+        self.info(tid).priority = priority.max(1);
+    }
+}
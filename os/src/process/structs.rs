@@ -1,28 +1,48 @@
-use crate::context::Context;
-use crate::memory_set::{ MemorySet, handler::ByFrame, attr::MemoryAttr};
-use crate::memory::frame_allocator::alloc_frames;
+use crate::context::{ Context, TrapFrame };
+use crate::memory_set::{ MemorySet, handler::{ ByFrame, ByFrameLazy, ByFile, Guard }, attr::MemoryAttr};
 use crate::consts::*;
-use crate::process::{ Tid, ExitCode };
-use alloc::{ sync::Arc, boxed::Box };
-use alloc::alloc::{ alloc, dealloc, Layout };
+use crate::process::{ Tid, Pid, ExitCode };
+use crate::sync::{ condvar::Condvar, mutex::{ Mutex as SleepMutex, UserMutex }, semaphore::Semaphore };
+use crate::fs::stdio::{ STDIN, STDOUT };
+use crate::interrupt::{ disable_and_store, restore };
+use crate::process::init_stack::*;
+use crate::memory::access_user;
+use alloc::{ sync::{ Arc, Weak }, boxed::Box, vec::Vec, string::String, collections::BTreeMap };
+use rcore_fs::file::File;
+use rcore_fs::vfs::INode;
+use crate::memory::slab::SlabCache;
+use crate::memory::shm::ShmSegment;
+use lazy_static::*;
 use riscv::register::satp;
+use spin::Mutex;
 use core::str;
+use core::mem;
+use core::sync::atomic::{ AtomicUsize, Ordering };
 
 use xmas_elf::{
     header,
-    program::{ Flags, SegmentData, Type },
+    program::{ Flags, Type },
     ElfFile,
 };
 
 pub struct KernelStack(usize);
 const STACK_SIZE: usize = 0x8000;
+// 写在栈底的魔数，释放时被改写说明发生了栈溢出
+const STACK_MAGIC: usize = 0x5ac0_ffee;
+
+fn init_kernel_stack(bottom: usize) {
+    unsafe { *(bottom as *mut usize) = STACK_MAGIC; }
+}
+
+// 内核栈从单独的 slab 缓存中分配，不占用内核堆
+lazy_static! {
+    pub static ref KSTACK_CACHE: SlabCache
+        = SlabCache::new("kstack", STACK_SIZE, PAGE_SIZE, Some(init_kernel_stack));
+}
 
 impl KernelStack {
     pub fn new() -> KernelStack {
-        let bottom =
-            unsafe {
-                alloc(Layout::from_size_align(STACK_SIZE, STACK_SIZE).unwrap())
-            } as usize;
+        let bottom = KSTACK_CACHE.alloc().expect("failed to alloc kernel stack");
         KernelStack(bottom)
     }
 
@@ -33,17 +53,235 @@ impl KernelStack {
 
 impl Drop for KernelStack {
     fn drop(&mut self) {
-        unsafe {
-            dealloc(
-                self.0 as _,
-                Layout::from_size_align(STACK_SIZE, STACK_SIZE).unwrap()
-            );
-        }
+        assert_eq!(unsafe { *(self.0 as *const usize) }, STACK_MAGIC, "kernel stack overflow");
+        KSTACK_CACHE.dealloc(self.0);
+    }
+}
+
+// 读写文件时可能会阻塞，因此用可以睡眠的锁保护
+pub type FileTable = BTreeMap<usize, Arc<SleepMutex<File>>>;
+
+// 用户堆的范围，end 即 brk 的当前值
+#[derive(Clone, Copy)]
+pub struct UserHeap {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl UserHeap {
+    pub fn new(start: usize) -> Self {
+        UserHeap { start, end: start }
     }
 }
 
 pub struct Process {
-    vm: Arc<MemorySet>,
+    pub pid: Pid,
+    pub vm: Arc<Mutex<MemorySet>>,
+    pub heap: Mutex<UserHeap>,
+    pub files: Mutex<FileTable>,    // 文件描述符表
+    pub mutexes: Mutex<Vec<Arc<UserMutex>>>,        // 用户程序创建的互斥锁，编号即下标
+    pub semaphores: Mutex<Vec<Arc<Semaphore>>>,     // 用户程序创建的信号量，编号即下标
+    pub shms: Mutex<Vec<Option<Arc<ShmSegment>>>>,  // 用户程序取得的共享内存段，编号即下标，取消挂载后编号失效
+    pub inner: Mutex<ProcessInner>,
+    pub child_exit: Condvar,    // 有子进程退出时通知父进程
+    pub thread_exit: Condvar,   // 有线程退出时通知 join 的线程
+}
+
+pub struct ProcessInner {
+    pub parent: Weak<Process>,
+    pub children: Vec<Arc<Process>>,
+    pub exit_code: Option<ExitCode>,    // 进程退出后成为僵尸进程，保存退出码直到被父进程回收
+    pub threads: Vec<Tid>,  // 进程中还在运行的线程，最后一个线程退出或进程被结束时进程退出
+    pub exited_threads: BTreeMap<Tid, ExitCode>,    // 已经退出但还没有被 join 的线程
+    pub adopted: bool,  // 是否是被收养的孤儿进程，这样的进程退出时直接被回收
+    pub killed: Option<ExitCode>,   // 进程被结束时的退出码，其余线程都退出之后进程才以它退出
+}
+
+static PID_COUNTER: AtomicUsize = AtomicUsize::new(1);
+
+impl Process {
+    pub fn new(vm: MemorySet, heap: UserHeap, files: FileTable, parent: Option<&Arc<Process>>) -> Arc<Process> {
+        let proc = Arc::new(Process{
+            pid: PID_COUNTER.fetch_add(1, Ordering::Relaxed),
+            vm: Arc::new(Mutex::new(vm)),
+            heap: Mutex::new(heap),
+            files: Mutex::new(files),
+            mutexes: Mutex::new(Vec::new()),
+            semaphores: Mutex::new(Vec::new()),
+            shms: Mutex::new(Vec::new()),
+            inner: Mutex::new(ProcessInner{
+                parent: parent.map_or(Weak::new(), Arc::downgrade),
+                children: Vec::new(),
+                exit_code: None,
+                threads: Vec::new(),
+                exited_threads: BTreeMap::new(),
+                adopted: false,
+                killed: None,
+            }),
+            child_exit: Condvar::new(),
+            thread_exit: Condvar::new(),
+        });
+        if let Some(parent) = parent {
+            parent.inner.lock().children.push(proc.clone());
+        }
+        proc
+    }
+
+    // 新进程的 0、1、2 号文件描述符分别为标准输入、标准输出、标准错误
+    pub fn stdio_files() -> FileTable {
+        let mut files = FileTable::new();
+        files.insert(0, Arc::new(SleepMutex::new(File::new(STDIN.clone(), true, false))));
+        files.insert(1, Arc::new(SleepMutex::new(File::new(STDOUT.clone(), false, true))));
+        files.insert(2, Arc::new(SleepMutex::new(File::new(STDOUT.clone(), false, true))));
+        files
+    }
+
+    // 分配最小的空闲文件描述符
+    pub fn add_file(&self, file: File) -> usize {
+        let mut files = self.files.lock();
+        let fd = (0..).find(|fd| !files.contains_key(fd)).unwrap();
+        files.insert(fd, Arc::new(SleepMutex::new(file)));
+        fd
+    }
+
+    pub fn get_file(&self, fd: usize) -> Option<Arc<SleepMutex<File>>> {
+        self.files.lock().get(&fd).cloned()
+    }
+
+    pub fn remove_file(&self, fd: usize) -> Option<Arc<SleepMutex<File>>> {
+        self.files.lock().remove(&fd)
+    }
+
+    pub fn exit_code(&self) -> Option<ExitCode> {
+        self.inner.lock().exit_code
+    }
+
+    // 结束进程：记录退出码，此后各个线程在回到用户态之前退出。已经被结束时保留原来的退出码
+    pub fn kill(&self, code: ExitCode) {
+        let mut inner = self.inner.lock();
+        if inner.killed.is_none() {
+            inner.killed = Some(code);
+        }
+    }
+
+    pub fn killed(&self) -> Option<ExitCode> {
+        self.inner.lock().killed
+    }
+
+    // 进程中还在运行的线程
+    pub fn threads(&self) -> Vec<Tid> {
+        self.inner.lock().threads.clone()
+    }
+
+    // 记录新加入线程池的线程 tid
+    pub fn add_thread(&self, tid: Tid) {
+        let mut inner = self.inner.lock();
+        inner.threads.push(tid);
+        // tid 可能是重复利用的，之前同一 tid 的线程退出记录已经没有意义
+        inner.exited_threads.remove(&tid);
+    }
+
+    // 线程退出：记录退出码并通知 join 的线程，返回它是否是进程中最后一个线程
+    pub fn exit_thread(&self, tid: Tid, code: ExitCode) -> bool {
+        let mut inner = self.inner.lock();
+        inner.threads.retain(|&t| t != tid);
+        inner.exited_threads.insert(tid, code);
+        let last = inner.threads.is_empty();
+        drop(inner);
+        self.thread_exit.notify_all();
+        last
+    }
+
+    // 等待同一进程中的线程 tid 退出并返回其退出码，tid 不是本进程的线程或进程已经被结束时返回 None
+    pub fn join(&self, tid: Tid) -> Option<ExitCode> {
+        loop {
+            // 与 wait 相同，从检查到进入等待队列之间不能被中断
+            let flags = disable_and_store();
+            let mut inner = self.inner.lock();
+            if inner.killed.is_some() {
+                drop(inner);
+                restore(flags);
+                return None;
+            }
+            if let Some(code) = inner.exited_threads.remove(&tid) {
+                drop(inner);
+                restore(flags);
+                return Some(code);
+            }
+            if !inner.threads.contains(&tid) {
+                drop(inner);
+                restore(flags);
+                return None;
+            }
+            drop(inner);
+            self.thread_exit.wait();
+            restore(flags);
+        }
+    }
+
+    // 进程退出：记录退出码，把子进程交给 init 进程收养，并通知父进程
+    // 由最后一个退出的线程调用，此时已经没有线程会再使用这个地址空间
+    pub fn exit(&self, code: ExitCode, init: Option<Arc<Process>>) {
+        self.files.lock().clear();  // 关闭所有打开的文件
+        // 释放用户内存。当前仍在使用这个页表，页表本身在进程被回收时才释放
+        self.vm.lock().clear();
+        self.shms.lock().clear();   // 没有其它进程使用的共享内存段随之释放
+        let mut inner = self.inner.lock();
+        inner.exit_code = Some(code);
+        let children = mem::replace(&mut inner.children, Vec::new());
+        let parent = inner.parent.upgrade();
+        let adopted = inner.adopted;
+        drop(inner);
+        // 已经退出的子进程没有进程再等待它，直接回收。其余的交给 init 进程收养，没有 init 进程时在退出后自行回收
+        let init = init.filter(|init| init.pid != self.pid);
+        for child in children {
+            let mut child_inner = child.inner.lock();
+            if child_inner.exit_code.is_some() {
+                continue;
+            }
+            child_inner.adopted = true;
+            child_inner.parent = init.as_ref().map_or(Weak::new(), Arc::downgrade);
+            drop(child_inner);
+            if let Some(init) = init.as_ref() {
+                init.inner.lock().children.push(child);
+            }
+        }
+        if let Some(parent) = parent {
+            // init 进程只等待自己创建的子进程，被收养的进程不会成为僵尸进程
+            if adopted {
+                parent.inner.lock().children.retain(|child| child.pid != self.pid);
+            }
+            parent.child_exit.notify();
+        }
+    }
+
+    // 等待子进程退出并回收，pid 为 None 时等待任意一个子进程
+    // 没有符合条件的子进程或自己已经被结束时返回 None
+    pub fn wait(&self, pid: Option<Pid>) -> Option<(Pid, ExitCode)> {
+        let is_target = |child: &Arc<Process>| pid.map_or(true, |pid| child.pid == pid);
+        loop {
+            // 关中断，避免在检查之后、进入等待队列之前退出的子进程的通知丢失
+            let flags = disable_and_store();
+            let mut inner = self.inner.lock();
+            if inner.killed.is_some() || !inner.children.iter().any(|child| is_target(child)) {
+                drop(inner);
+                restore(flags);
+                return None;
+            }
+            if let Some(idx) = inner.children
+                .iter()
+                .position(|child| is_target(child) && child.exit_code().is_some())
+            {
+                let child = inner.children.remove(idx);
+                drop(inner);
+                restore(flags);
+                return Some((child.pid, child.exit_code().unwrap()));
+            }
+            drop(inner);
+            self.child_exit.wait();
+            restore(flags);
+        }
+    }
 }
 
 pub struct Thread {
@@ -74,40 +312,56 @@ impl Thread {
         }
     }
 
-    pub unsafe fn new_user(data: &[u8]) -> Box<Thread> 
+    // data 为 inode 的内容，不是可执行的 ELF 文件时返回 None
+    pub unsafe fn new_user(inode: &Arc<INode>, data: &[u8], args: Vec<String>, envs: Vec<String>, parent: Option<&Arc<Process>>) -> Option<Box<Thread>>
     {
-        let elf = ElfFile::new(data).expect("failed to read elf");
+        let image = UserImage::load(inode, data, args, envs)?;
+        let kstack = KernelStack::new();    //　为用户程序创建内核栈。用于线程切换
+        Some(Box::new(Thread{    // 注意下面创建上下文使用的是哪个栈
+            context: Context::new_user_thread(image.entry, image.sp, [image.argc, image.argv, image.envp], kstack.top(), image.vm.token()),
+            kstack: kstack,
+            proc: Some(Process::new(image.vm, UserHeap::new(image.heap_start), Process::stdio_files(), parent)),
+        }))
+    }
 
-        // Check ELF type
-        match elf.header.pt2.type_().as_type() {
-            header::Type::Executable => {println!("it really a elf");},
-            header::Type::SharedObject => {},
-            _ => panic!("ELF is not executable or shared object"),
-        }
+    pub fn fork(&self, tf: &TrapFrame) -> Box<Thread> {
+        let proc = self.proc.as_ref().expect("kernel thread can not fork");
+        let vm = proc.vm.lock().clone();    // 以写时复制的方式复制地址空间
+        let heap = *proc.heap.lock();
+        let files = proc.files.lock().clone();  // 子进程与父进程共享打开的文件及其读写位置
+        let kstack = KernelStack::new();
+        Box::new(Thread{
+            context: unsafe { Context::new_fork(tf, kstack.top(), vm.token()) },
+            kstack: kstack,
+            proc: Some(Process::new(vm, heap, files, Some(proc))),
+        })
+    }
 
-        // entry_point 代表程序入口在文件中的具体位置
-        let entry_addr = elf.header.pt2.entry_point() as usize;
-        println!("entry: {:#x}", entry_addr);
-        let mut vm = elf.make_memory_set(); // 为这个 elf 文件创建一个新的虚存系统，其中包含内核的地址空间和elf文件中程序的地址空间
-        let mut ustack_top = {  // 创建用户栈
-            let (ustack_buttom, ustack_top) = (USER_STACK_OFFSET, USER_STACK_OFFSET + USER_STACK_SIZE);
-            let paddr = alloc_frames(USER_STACK_SIZE / PAGE_SIZE).unwrap().start_address().as_usize();
-            vm.push(    // 创建一个内核栈之后还需要将这个内核栈装入虚存系统。
-                ustack_buttom,
-                ustack_top,
-                MemoryAttr::new().set_user(),
-                ByFrame::new(),
-            );
-            ustack_top
-        };
+    // 在当前进程中创建一个新线程，从 entry 开始执行，栈顶为 ustack_top，arg 作为第一个参数
+    pub fn new_thread(&self, entry: usize, ustack_top: usize, arg: usize) -> Box<Thread> {
+        let proc = self.proc.as_ref().expect("kernel thread can not create user thread");
+        let kstack = KernelStack::new();
+        let token = proc.vm.lock().token();
+        Box::new(Thread{
+            context: unsafe { Context::new_user_thread(entry, ustack_top, [arg, 0, 0], kstack.top(), token) },
+            kstack: kstack,
+            proc: Some(proc.clone()),
+        })
+    }
 
-        let kstack = KernelStack::new();    //　为用户程序创建内核栈。用于线程切换
-        Box::new(Thread{    // 注意下面创建上下文使用的是哪个栈
-            context: Context::new_user_thread(entry_addr, ustack_top, kstack.top(), vm.token()),
+    // 与 fork 类似，但新线程与当前线程共享进程，在新的栈 ustack_top 上返回 0。ustack_top 为 0 时使用原来的栈
+    pub fn clone_thread(&self, tf: &TrapFrame, ustack_top: usize) -> Box<Thread> {
+        let proc = self.proc.as_ref().expect("kernel thread can not clone");
+        let kstack = KernelStack::new();
+        let token = proc.vm.lock().token();
+        let mut tf = tf.clone();
+        if ustack_top != 0 {
+            tf.x[2] = ustack_top;
+        }
+        Box::new(Thread{
+            context: unsafe { Context::new_fork(&tf, kstack.top(), token) },
             kstack: kstack,
-            proc: Some(Arc::new(Process{
-                vm: Arc::new(vm),
-            })),
+            proc: Some(proc.clone()),
         })
     }
 
@@ -126,12 +380,142 @@ pub enum Status {
     Exited(ExitCode),
 }
 
+// 从 ELF 文件创建的用户地址空间及其初始栈
+pub struct UserImage {
+    pub vm: MemorySet,
+    pub entry: usize,
+    pub heap_start: usize,  // 所有段之后的第一页，作为用户堆的起点
+    pub sp: usize,
+    pub argc: usize,
+    pub argv: usize,
+    pub envp: usize,
+}
+
+impl UserImage {
+    // data 为 inode 的内容，只读的段直接从 inode 映射
+    pub fn load(inode: &Arc<INode>, data: &[u8], args: Vec<String>, envs: Vec<String>) -> Option<UserImage> {
+        let elf = match ElfFile::new(data) {
+            Ok(elf) => elf,
+            Err(err) => {
+                println!("failed to read elf: {}", err);
+                return None;
+            },
+        };
+
+        // Check ELF type
+        match elf.header.pt2.type_().as_type() {
+            header::Type::Executable => {println!("it really a elf");},
+            header::Type::SharedObject => {},
+            _ => {
+                println!("ELF is not executable or shared object");
+                return None;
+            },
+        }
+
+        // 程序头表必须完整地位于文件中，否则枚举程序段时会越界
+        let ph_end = elf.header.pt2.ph_offset()
+            .checked_add(elf.header.pt2.ph_count() as u64 * elf.header.pt2.ph_entry_size() as u64);
+        if ph_end.map_or(true, |end| end > data.len() as u64) {
+            println!("program header table out of file");
+            return None;
+        }
+
+        // entry_point 代表程序入口在文件中的具体位置
+        let entry_addr = elf.header.pt2.entry_point() as usize;
+        println!("entry: {:#x}", entry_addr);
+
+        let init_stack = InitStack {
+            args,
+            envs,
+            auxv: {
+                let mut auxv = Vec::new();
+                if let Some(phdr) = elf.phdr_addr() {
+                    auxv.push((AT_PHDR, phdr));
+                }
+                auxv.push((AT_PHENT, elf.header.pt2.ph_entry_size() as usize));
+                auxv.push((AT_PHNUM, elf.header.pt2.ph_count() as usize));
+                auxv.push((AT_PAGESZ, PAGE_SIZE));
+                auxv.push((AT_ENTRY, entry_addr));
+                auxv
+            },
+        };
+        if init_stack.size() > USER_STACK_SIZE / 2 {
+            println!("arguments too long");
+            return None;
+        }
+
+        let mut vm = elf.make_memory_set(inode)?; // 为这个 elf 文件创建一个新的虚存系统，其中包含内核的地址空间和elf文件中程序的地址空间
+        let ustack_top = USER_STACK_OFFSET + USER_STACK_SIZE;
+        // 用户栈下方保留一个不可访问的保护页，栈溢出时触发缺页异常而不是覆盖其它数据
+        vm.push(
+            USER_STACK_OFFSET - PAGE_SIZE,
+            USER_STACK_OFFSET,
+            MemoryAttr::new(),
+            Guard::new(),
+        );
+        let stack = init_stack.build(ustack_top);
+        {  // 创建用户栈
+            // 存放初始栈内容的页立即分配，其余部分在访问时才分配页帧
+            let init_bottom = (stack.sp) & !(PAGE_SIZE - 1);
+            if init_bottom > USER_STACK_OFFSET {
+                vm.push(
+                    USER_STACK_OFFSET,
+                    init_bottom,
+                    MemoryAttr::new().set_user(),
+                    ByFrameLazy::new(),
+                );
+            }
+            vm.push(
+                init_bottom,
+                ustack_top,
+                MemoryAttr::new().set_user(),
+                ByFrame::new(),
+            );
+            let target = unsafe { ::core::slice::from_raw_parts_mut(stack.sp as *mut u8, stack.data.len()) };
+            unsafe {
+                vm.with(|| access_user(|| target.copy_from_slice(&stack.data)));
+            }
+        }
+
+        Some(UserImage {
+            vm,
+            entry: entry_addr,
+            heap_start: elf.heap_start(),
+            sp: stack.sp,
+            argc: stack.argc,
+            argv: stack.argv,
+            envp: stack.envp,
+        })
+    }
+}
+
 trait ElfExt {
-    fn make_memory_set(&self) -> MemorySet;
+    // 段的权限不合法时返回 None
+    fn make_memory_set(&self, inode: &Arc<INode>) -> Option<MemorySet>;
+    fn phdr_addr(&self) -> Option<usize>;
+    fn heap_start(&self) -> usize;
 }
 
 impl ElfExt for ElfFile<'_> {
-    fn make_memory_set(&self) -> MemorySet {
+    // 程序头表被某个段装入内存时，返回其虚拟地址
+    fn phdr_addr(&self) -> Option<usize> {
+        let phoff = self.header.pt2.ph_offset() as usize;
+        self.program_iter()
+            .filter(|ph| ph.get_type() == Ok(Type::Load))
+            .find(|ph| ph.offset() as usize <= phoff && phoff < (ph.offset() + ph.file_size()) as usize)
+            .map(|ph| ph.virtual_addr() as usize + phoff - ph.offset() as usize)
+    }
+
+    fn heap_start(&self) -> usize {
+        let end = self.program_iter()
+            .filter(|ph| ph.get_type() == Ok(Type::Load))
+            .map(|ph| (ph.virtual_addr() + ph.mem_size()) as usize)
+            .max()
+            .unwrap_or(0);
+        (end + PAGE_SIZE - 1) & !(PAGE_SIZE - 1)
+    }
+
+    fn make_memory_set(&self, inode: &Arc<INode>) -> Option<MemorySet> {
         println!("creating MemorySet from ELF");
         let mut ms = MemorySet::new_kern(); // 创建自带内核地址空间的虚拟存储系统
 
@@ -139,37 +523,91 @@ impl ElfExt for ElfFile<'_> {
             if ph.get_type() != Ok(Type::Load) {
                 continue;
             }
+            // 段在文件中的部分必须完整地位于文件中，在内存中的部分必须位于用户栈的保护页之下，且不能与其它段重叠
+            let in_file = ph.offset()
+                .checked_add(ph.file_size())
+                .map_or(false, |end| end <= self.input.len() as u64);
+            let in_user = ph.virtual_addr()
+                .checked_add(ph.mem_size())
+                .map_or(false, |end| end <= (USER_STACK_OFFSET - PAGE_SIZE) as u64);
+            if !in_file || !in_user || ph.file_size() > ph.mem_size() {
+                println!("invalid segment at {:#x}", ph.virtual_addr());
+                return None;
+            }
+            if ph.mem_size() == 0 {
+                continue;
+            }
             // 获取程序段的大小和起始地址(虚拟的)
             let virt_addr = ph.virtual_addr() as usize;
             let mem_size = ph.mem_size() as usize;
+            if !ms.test_free_area(virt_addr, virt_addr + mem_size) {
+                println!("segment at {:#x} overlaps with another segment", virt_addr);
+                return None;
+            }
+            if ph.flags().is_write() && ph.flags().is_execute() && !ALLOW_WRITABLE_EXEC {
+                println!("segment at {:#x} is both writable and executable", virt_addr);
+                return None;
+            }
+            let attr = ph.flags().to_attr();
+            let offset = ph.offset() as usize;
+            let mem_end = virt_addr + mem_size;
+
+            // 只读的段在第一次访问时才从文件中读入，文件中的偏移与虚拟地址需要在页内对齐
+            // 段可能从页的中间开始，映射按页进行，起始地址和偏移都向下取整到页的开头
+            if !ph.flags().is_write() && offset % PAGE_SIZE == virt_addr % PAGE_SIZE {
+                println!("virt_addr {:#x}, mem_size {:#x}, mapped from file", virt_addr, mem_size);
+                let file_end = virt_addr + ph.file_size() as usize;
+                ms.push(
+                    virt_addr,
+                    mem_end,
+                    attr,
+                    ByFile::new(inode.clone(), virt_addr & !(PAGE_SIZE - 1), offset & !(PAGE_SIZE - 1), file_end, false, false),
+                );
+                continue;
+            }
+
             // 将数据读取为 u8 的数组
-            let data = match ph.get_data(self).unwrap() {
-                SegmentData::Undefined(data) => data,
-                _ => unreachable!(),
-            };
+            let data = &self.input[offset..offset + ph.file_size() as usize];
+
+            // 文件中有数据的页立即分配并拷贝，之后剩余的 .bss 部分按需分配
+            let data_end = ((virt_addr + data.len() + PAGE_SIZE - 1) & !(PAGE_SIZE - 1)).min(mem_end);
 
             // Get target slice
             let target = {  // 可以看到，这里的 virt_addr 是根据文件中的虚拟地址得到的，所以 target 应该仅用于 with 函数中
                 println!("virt_addr {:#x}, mem_size {:#x}", virt_addr, mem_size);
-                ms.push(
-                    virt_addr,
-                    virt_addr + mem_size,
-                    ph.flags().to_attr(),
-                    ByFrame::new(),
-                );
-                unsafe { ::core::slice::from_raw_parts_mut(virt_addr as *mut u8, mem_size) }
+                if data_end > virt_addr {
+                    // 拷贝数据时需要写权限，拷贝完成后再设置为段要求的权限
+                    ms.push(
+                        virt_addr,
+                        data_end,
+                        MemoryAttr::new().set_user(),
+                        ByFrame::new(),
+                    );
+                }
+                if mem_end > data_end {
+                    ms.push(
+                        data_end,
+                        mem_end,
+                        attr.clone(),
+                        ByFrameLazy::new(),
+                    );
+                }
+                unsafe { ::core::slice::from_raw_parts_mut(virt_addr as *mut u8, data_end - virt_addr) }
             };
             // Copy data
             unsafe {
-                ms.with(|| {    // with 函数的作用是，将当前这个未激活页表激活并执行一个函数，然后切换回原来的页表
+                ms.with(|| access_user(|| {    // with 函数的作用是，将当前这个未激活页表激活并执行一个函数，然后切换回原来的页表
                     if data.len() != 0 {
                         target[..data.len()].copy_from_slice(data);
                     }
                     target[data.len()..].iter_mut().for_each(|x| *x = 0);
-                });
+                }));
+            }
+            if data_end > virt_addr {
+                ms.protect(virt_addr, data_end, attr);
             }
         }
-        ms
+        Some(ms)
     }
 }
 
@@ -180,6 +618,9 @@ trait ToMemoryAttr {
 impl ToMemoryAttr for Flags {
     fn to_attr(&self) -> MemoryAttr {   // 将文件中各个段的读写权限转换为页表权限
         let mut flags = MemoryAttr::new().set_user();
+        if !self.is_write() {
+            flags = flags.set_readonly();
+        }
         if self.is_execute() {
             flags = flags.set_execute();
         }
@@ -10,20 +10,20 @@ pub struct ThreadInfo {
 
 pub struct ThreadPool {
     pub threads: Vec<Option<ThreadInfo>>, // 线程信号量的向量
-    scheduler: Box<Scheduler>, // 调度算法
+    scheduler: Box<Scheduler>, // 调度算法，实现了 Scheduler 接口即可替换
 }
 
 use crate::process::Tid;
 
 impl ThreadPool {
-    pub fn new(size: usize, scheduler: Scheduler) -> ThreadPool {
+    pub fn new(size: usize, scheduler: Box<Scheduler>) -> ThreadPool {
         ThreadPool {
             threads: {
                 let mut th = Vec::new();
                 th.resize_with(size, Default::default);
                 th
             },
-            scheduler: Box::new(scheduler),
+            scheduler: scheduler,
         }
     }
 
@@ -36,7 +36,7 @@ impl ThreadPool {
         panic!("alloc tid failed !");
     }
 
-    pub fn add(&mut self, _thread: Box<Thread>) {
+    pub fn add(&mut self, _thread: Box<Thread>) -> Tid {
         let tid = self.alloc_tid();
         self.threads[tid] = Some(ThreadInfo{
             status: Status::Ready,
@@ -45,6 +45,7 @@ impl ThreadPool {
         });
         self.scheduler.push(tid);
         println!("tid to alloc: {}", tid);
+        tid
     }
 
     pub fn acquire(&mut self) -> Option<(Tid, Box<Thread>)> {
@@ -86,11 +87,21 @@ impl ThreadPool {
         println!("exit code: {}", code);
     }
 
+    pub fn set_priority(&mut self, tid: Tid, priority: usize) {
+        self.scheduler.set_priority(tid, priority);
+    }
+
+    // 只唤醒正在睡眠的线程，定时器和条件变量可能重复唤醒同一个线程
     pub fn wakeup(&mut self, tid: Tid) {
-        let proc = self.threads[tid].as_mut().expect("thread not exist");
+        let proc = match self.threads[tid].as_mut() {
+            Some(proc) => proc,
+            None => return,
+        };
         if proc.present {
-            proc.status = Status::Ready;
-            self.scheduler.push(tid);
+            if let Status::Sleeping = proc.status {
+                proc.status = Status::Ready;
+                self.scheduler.push(tid);
+            }
         } else {
             panic!("try to sleep an null thread !");
         }
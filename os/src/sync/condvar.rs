@@ -1,10 +1,13 @@
-use spin::Mutex;
+use super::spinlock::IrqSpinLock;
 use alloc::{ collections::VecDeque, };
 use crate::process::{ Tid, current_tid, yield_now, wake_up };
+use crate::timer::{ add_timer, cancel_timer, TimerEvent };
+use crate::clock::get_tick;
+use crate::interrupt::{ disable_and_store, restore };
 
 #[derive(Default)]
 pub struct Condvar {
-    wait_queue: Mutex<VecDeque<Tid>>,
+    wait_queue: IrqSpinLock<VecDeque<Tid>>,   // 中断处理函数中也会调用 notify
 }
 
 impl Condvar {
@@ -12,19 +15,65 @@ impl Condvar {
         Condvar::default()
     }
 
+    // 从进入等待队列到睡眠之间不能被中断，否则在此期间到来的唤醒会因为线程还没有睡眠而丢失
+    // 进程被结束时线程会被直接唤醒，这时自己还在等待队列中，需要移除
     pub fn wait(&self) {
-        let mut queue = self.wait_queue.lock();
-        queue.push_back(current_tid());
-        drop(queue);
+        let tid = current_tid();
+        let flags = disable_and_store();
+        self.wait_queue.lock().push_back(tid);
         yield_now();
+        restore(flags);
+        self.wait_queue.lock().retain(|&t| t != tid);
     }
 
+    // 最多等待 ticks 个时钟周期，返回是否是被 notify 唤醒的
+    pub fn wait_timeout(&self, ticks: usize) -> bool {
+        let tid = current_tid();
+        let flags = disable_and_store();
+        self.wait_queue.lock().push_back(tid);
+        let timer = add_timer(get_tick().saturating_add(ticks), TimerEvent::Wakeup(tid));
+        yield_now();
+        restore(flags);
+        cancel_timer(timer);
+        // 超时唤醒时自己还在等待队列中，需要移除
+        let mut queue = self.wait_queue.lock();
+        match queue.iter().position(|&t| t == tid) {
+            Some(idx) => {
+                queue.remove(idx);
+                false
+            },
+            None => true,
+        }
+    }
+
+    // 只唤醒等待的线程，不让出 CPU：在线程中调用时 yield_now 会让通知者自己睡眠
     pub fn notify(&self) {
         let mut queue = self.wait_queue.lock();
         if let Some(tid) = queue.pop_front() {
-            wake_up(tid);
             drop(queue);
-            yield_now();
+            wake_up(tid);
+        }
+    }
+
+    // 唤醒至多 n 个等待的线程，返回唤醒的数量
+    pub fn notify_n(&self, n: usize) -> usize {
+        let mut queue = self.wait_queue.lock();
+        let mut count = 0;
+        while count < n {
+            match queue.pop_front() {
+                Some(tid) => wake_up(tid),
+                None => break,
+            }
+            count += 1;
+        }
+        count
+    }
+
+    // 唤醒所有等待的线程
+    pub fn notify_all(&self) {
+        let mut queue = self.wait_queue.lock();
+        while let Some(tid) = queue.pop_front() {
+            wake_up(tid);
         }
     }
 }
@@ -0,0 +1,54 @@
+use alloc::{ collections::BTreeMap, sync::Arc };
+use spin::Mutex;
+use lazy_static::*;
+use crate::consts::PAGE_SIZE;
+use super::condvar::Condvar;
+
+// futex 的等待队列，以用户内存字的物理地址为键，共享映射的不同虚拟地址对应同一个队列
+lazy_static! {
+    static ref QUEUES: Mutex<BTreeMap<usize, Arc<Condvar>>> = Mutex::new(BTreeMap::new());
+}
+
+// 没有线程等待的队列从表中删除
+fn release_queue(key: usize, queue: Arc<Condvar>) {
+    let mut queues = QUEUES.lock();
+    drop(queue);
+    if queues.get(&key).map_or(false, |queue| Arc::strong_count(queue) == 1) {
+        queues.remove(&key);
+    }
+}
+
+// 物理地址为 frame 的页上是否有线程在等待。有等待者的页不能换出，否则换入后物理地址改变，
+// 等待者就再也不会被唤醒。换出时可能正在持有队列表的锁，这时保守地认为有等待者
+pub fn has_waiters(frame: usize) -> bool {
+    match QUEUES.try_lock() {
+        Some(queues) => queues.range(frame..frame + PAGE_SIZE).next().is_some(),
+        None => true,
+    }
+}
+
+// 在 key 对应的队列上等待，最多等待 ticks 个时钟周期。返回是否是被 wake 唤醒的
+// 调用者需要在检查内存字之后、调用之前关闭中断，以免错过唤醒
+pub fn wait(key: usize, ticks: Option<usize>) -> bool {
+    let queue = QUEUES.lock().entry(key).or_insert_with(|| Arc::new(Condvar::new())).clone();
+    let woken = match ticks {
+        Some(ticks) => queue.wait_timeout(ticks),
+        None => {
+            queue.wait();
+            true
+        },
+    };
+    release_queue(key, queue);
+    woken
+}
+
+// 唤醒 key 对应队列上至多 count 个线程，返回唤醒的数量
+pub fn wake(key: usize, count: usize) -> usize {
+    let queue = match QUEUES.lock().get(&key) {
+        Some(queue) => queue.clone(),
+        None => return 0,
+    };
+    let woken = queue.notify_n(count);
+    release_queue(key, queue);
+    woken
+}
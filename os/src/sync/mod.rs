@@ -1 +1,6 @@
-pub mod condvar;
\ No newline at end of file
+pub mod condvar;
+pub mod futex;
+pub mod mutex;
+pub mod rwlock;
+pub mod semaphore;
+pub mod spinlock;
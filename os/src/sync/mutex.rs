@@ -0,0 +1,150 @@
+use core::cell::UnsafeCell;
+use core::mem;
+use core::ops::{ Deref, DerefMut };
+use core::sync::atomic::{ AtomicBool, Ordering };
+use crate::interrupt::{ disable_and_store, restore };
+use crate::process::{ self, Tid };
+use super::condvar::Condvar;
+
+// 获取不到锁时睡眠等待的互斥锁，可以在持有期间让出 CPU
+pub struct Mutex<T: ?Sized> {
+    locked: AtomicBool,
+    wait_queue: Condvar,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+
+pub struct MutexGuard<'a, T: ?Sized> {
+    mutex: &'a Mutex<T>,
+}
+
+impl<T> Mutex<T> {
+    pub fn new(data: T) -> Self {
+        Mutex {
+            locked: AtomicBool::new(false),
+            wait_queue: Condvar::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    pub fn lock(&self) -> MutexGuard<T> {
+        loop {
+            // 关中断，避免检查锁之后、进入等待队列之前被唤醒而丢失通知
+            let flags = disable_and_store();
+            if let Some(guard) = self.try_lock() {
+                restore(flags);
+                return guard;
+            }
+            self.wait_queue.wait();
+            restore(flags);
+        }
+    }
+
+    // 与 lock 相同，但所在的进程被结束时放弃等待，返回 None。用于可能长时间等待的用户请求
+    pub fn lock_killable(&self) -> Option<MutexGuard<T>> {
+        loop {
+            let flags = disable_and_store();
+            if process::killed() {
+                restore(flags);
+                return None;
+            }
+            if let Some(guard) = self.try_lock() {
+                restore(flags);
+                return Some(guard);
+            }
+            self.wait_queue.wait();
+            restore(flags);
+        }
+    }
+
+    pub fn try_lock(&self) -> Option<MutexGuard<T>> {
+        if self.locked.swap(true, Ordering::Acquire) {
+            None
+        } else {
+            Some(MutexGuard { mutex: self })
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+
+    // 释放锁并唤醒一个等待的线程。用于锁的持有者不是一个 guard 的情况，调用者需要保证锁是自己持有的
+    pub unsafe fn force_unlock(&self) {
+        self.locked.store(false, Ordering::Release);
+        self.wait_queue.notify();
+    }
+}
+
+impl<T: ?Sized + Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Mutex::new(T::default())
+    }
+}
+
+impl<'a, T: ?Sized> Deref for MutexGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for MutexGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for MutexGuard<'a, T> {
+    fn drop(&mut self) {
+        unsafe { self.mutex.force_unlock() };
+    }
+}
+
+// 用户程序通过系统调用加锁和解锁的互斥锁。两次调用之间没有 guard，需要记录持有锁的线程
+pub struct UserMutex {
+    mutex: Mutex<()>,
+    owner: spin::Mutex<Option<Tid>>,
+}
+
+impl UserMutex {
+    pub fn new() -> Self {
+        UserMutex {
+            mutex: Mutex::new(()),
+            owner: spin::Mutex::new(None),
+        }
+    }
+
+    // 线程 tid 加锁，锁会一直持有到 unlock。等待期间所在的进程被结束时返回 false
+    pub fn lock(&self, tid: Tid) -> bool {
+        match self.mutex.lock_killable() {
+            Some(guard) => {
+                mem::forget(guard);
+                *self.owner.lock() = Some(tid);
+                true
+            },
+            None => false,
+        }
+    }
+
+    // 锁的持有者，没有被锁住时返回 None
+    pub fn owner(&self) -> Option<Tid> {
+        *self.owner.lock()
+    }
+
+    // 线程 tid 解锁，只有持有者可以解锁，否则返回 false
+    pub fn unlock(&self, tid: Tid) -> bool {
+        let mut owner = self.owner.lock();
+        if *owner != Some(tid) {
+            return false;
+        }
+        *owner = None;
+        drop(owner);
+        unsafe { self.mutex.force_unlock(); }
+        true
+    }
+}
@@ -0,0 +1,135 @@
+use core::cell::UnsafeCell;
+use core::ops::{ Deref, DerefMut };
+use spin::Mutex;
+use crate::interrupt::{ disable_and_store, restore };
+use super::condvar::Condvar;
+
+struct RwState {
+    readers: usize,         // 持有读锁的线程数
+    writer: bool,           // 是否有线程持有写锁
+    waiting_writers: usize, // 等待写锁的线程数，有写者等待时不再让新的读者进入，避免写者饥饿
+}
+
+// 获取不到锁时睡眠等待的读写锁
+pub struct RwLock<T: ?Sized> {
+    state: Mutex<RwState>,
+    wait_queue: Condvar,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+pub struct RwLockReadGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+}
+
+pub struct RwLockWriteGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+}
+
+impl<T> RwLock<T> {
+    pub fn new(data: T) -> Self {
+        RwLock {
+            state: Mutex::new(RwState { readers: 0, writer: false, waiting_writers: 0 }),
+            wait_queue: Condvar::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    pub fn read(&self) -> RwLockReadGuard<T> {
+        loop {
+            let flags = disable_and_store();
+            if let Some(guard) = self.try_read() {
+                restore(flags);
+                return guard;
+            }
+            self.wait_queue.wait();
+            restore(flags);
+        }
+    }
+
+    pub fn write(&self) -> RwLockWriteGuard<T> {
+        let mut waiting = false;
+        loop {
+            let flags = disable_and_store();
+            let mut state = self.state.lock();
+            if !state.writer && state.readers == 0 {
+                state.writer = true;
+                if waiting {
+                    state.waiting_writers -= 1;
+                }
+                drop(state);
+                restore(flags);
+                return RwLockWriteGuard { lock: self };
+            }
+            if !waiting {
+                state.waiting_writers += 1;
+                waiting = true;
+            }
+            drop(state);
+            self.wait_queue.wait();
+            restore(flags);
+        }
+    }
+
+    pub fn try_read(&self) -> Option<RwLockReadGuard<T>> {
+        let mut state = self.state.lock();
+        if state.writer || state.waiting_writers > 0 {
+            return None;
+        }
+        state.readers += 1;
+        Some(RwLockReadGuard { lock: self })
+    }
+
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<T>> {
+        let mut state = self.state.lock();
+        if state.writer || state.readers > 0 {
+            return None;
+        }
+        state.writer = true;
+        Some(RwLockWriteGuard { lock: self })
+    }
+}
+
+impl<'a, T: ?Sized> Deref for RwLockReadGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RwLockReadGuard<'a, T> {
+    fn drop(&mut self) {
+        let mut state = self.lock.state.lock();
+        state.readers -= 1;
+        let last = state.readers == 0;
+        drop(state);
+        // 最后一个读者离开时唤醒等待的写者
+        if last {
+            self.lock.wait_queue.notify_all();
+        }
+    }
+}
+
+impl<'a, T: ?Sized> Deref for RwLockWriteGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for RwLockWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RwLockWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.state.lock().writer = false;
+        self.lock.wait_queue.notify_all();
+    }
+}
@@ -0,0 +1,85 @@
+use spin::Mutex;
+use crate::interrupt::{ disable_and_store, restore };
+use crate::process;
+use super::condvar::Condvar;
+
+// 计数信号量，计数为 0 时 acquire 会睡眠等待
+pub struct Semaphore {
+    count: Mutex<usize>,
+    wait_queue: Condvar,
+}
+
+pub struct SemaphoreGuard<'a> {
+    sem: &'a Semaphore,
+}
+
+impl Semaphore {
+    pub fn new(count: usize) -> Self {
+        Semaphore {
+            count: Mutex::new(count),
+            wait_queue: Condvar::new(),
+        }
+    }
+
+    // P 操作
+    pub fn acquire(&self) {
+        loop {
+            let flags = disable_and_store();
+            if self.try_acquire() {
+                restore(flags);
+                return;
+            }
+            self.wait_queue.wait();
+            restore(flags);
+        }
+    }
+
+    // 与 acquire 相同，但所在的进程被结束时放弃等待，返回 false
+    pub fn acquire_killable(&self) -> bool {
+        loop {
+            let flags = disable_and_store();
+            if process::killed() {
+                restore(flags);
+                return false;
+            }
+            if self.try_acquire() {
+                restore(flags);
+                return true;
+            }
+            self.wait_queue.wait();
+            restore(flags);
+        }
+    }
+
+    pub fn try_acquire(&self) -> bool {
+        let mut count = self.count.lock();
+        if *count > 0 {
+            *count -= 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    // V 操作
+    pub fn release(&self) {
+        *self.count.lock() += 1;
+        self.wait_queue.notify();
+    }
+
+    // acquire 之后返回一个 guard，guard 被释放时自动 release
+    pub fn access(&self) -> SemaphoreGuard {
+        self.acquire();
+        SemaphoreGuard { sem: self }
+    }
+
+    pub fn count(&self) -> usize {
+        *self.count.lock()
+    }
+}
+
+impl<'a> Drop for SemaphoreGuard<'a> {
+    fn drop(&mut self) {
+        self.sem.release();
+    }
+}
@@ -0,0 +1,75 @@
+use core::cell::UnsafeCell;
+use core::ops::{ Deref, DerefMut };
+use core::sync::atomic::{ AtomicBool, Ordering, spin_loop_hint };
+use crate::interrupt::{ disable_and_store, restore };
+
+// 持有期间关闭中断的自旋锁，用于保护中断处理函数中也会访问的数据
+// 否则线程持有锁时发生中断，中断处理函数再去获取同一把锁就会死锁
+pub struct IrqSpinLock<T: ?Sized> {
+    locked: AtomicBool,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for IrqSpinLock<T> {}
+unsafe impl<T: ?Sized + Send> Sync for IrqSpinLock<T> {}
+
+pub struct IrqSpinLockGuard<'a, T: ?Sized> {
+    lock: &'a IrqSpinLock<T>,
+    flags: usize,   // 加锁前的中断状态，释放锁时恢复
+}
+
+impl<T> IrqSpinLock<T> {
+    pub const fn new(data: T) -> Self {
+        IrqSpinLock {
+            locked: AtomicBool::new(false),
+            data: UnsafeCell::new(data),
+        }
+    }
+}
+
+impl<T: ?Sized> IrqSpinLock<T> {
+    // 先关中断再获取锁，guard 被释放时恢复原来的中断状态
+    pub fn lock(&self) -> IrqSpinLockGuard<T> {
+        let flags = disable_and_store();
+        while self.locked.swap(true, Ordering::Acquire) {
+            spin_loop_hint();
+        }
+        IrqSpinLockGuard { lock: self, flags }
+    }
+
+    pub fn try_lock(&self) -> Option<IrqSpinLockGuard<T>> {
+        let flags = disable_and_store();
+        if self.locked.swap(true, Ordering::Acquire) {
+            restore(flags);
+            None
+        } else {
+            Some(IrqSpinLockGuard { lock: self, flags })
+        }
+    }
+}
+
+impl<T: ?Sized + Default> Default for IrqSpinLock<T> {
+    fn default() -> Self {
+        IrqSpinLock::new(T::default())
+    }
+}
+
+impl<'a, T: ?Sized> Deref for IrqSpinLockGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for IrqSpinLockGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for IrqSpinLockGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.locked.store(false, Ordering::Release);
+        restore(self.flags);
+    }
+}
@@ -0,0 +1,312 @@
+use alloc::sync::Arc;
+use rcore_fs::file::{ File, SeekFrom };
+use rcore_fs::vfs::{ FileType, FsError, INode, Metadata };
+use crate::fs::{ ROOT_INODE, pipe::make_pipe };
+use crate::process::{ self, structs::Process };
+use crate::sync::mutex::{ Mutex as SleepMutex, MutexGuard };
+use super::*;
+use super::user::*;
+
+pub const AT_FDCWD: isize = -100;
+
+pub const O_RDONLY: usize = 0;
+pub const O_WRONLY: usize = 1;
+pub const O_RDWR: usize = 2;
+pub const O_CREAT: usize = 0x40;
+pub const O_TRUNC: usize = 0x200;
+
+pub const SEEK_SET: u8 = 0;
+pub const SEEK_CUR: u8 = 1;
+pub const SEEK_END: u8 = 2;
+
+// read 和 write 在内核中使用的缓冲区大小
+const IO_BUF_SIZE: usize = 0x1000;
+
+pub const S_IFIFO: u32 = 0o010000;
+pub const S_IFCHR: u32 = 0o020000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFBLK: u32 = 0o060000;
+pub const S_IFREG: u32 = 0o100000;
+pub const S_IFLNK: u32 = 0o120000;
+pub const S_IFSOCK: u32 = 0o140000;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Stat {
+    pub dev: u64,
+    pub ino: u64,
+    pub mode: u32,
+    pub nlink: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    pub blksize: u32,
+    pub blocks: u64,
+    pub atime: u64,
+    pub mtime: u64,
+    pub ctime: u64,
+}
+
+impl From<Metadata> for Stat {
+    fn from(info: Metadata) -> Self {
+        let type_ = match info.type_ {
+            FileType::File => S_IFREG,
+            FileType::Dir => S_IFDIR,
+            FileType::SymLink => S_IFLNK,
+            FileType::CharDevice => S_IFCHR,
+            FileType::BlockDevice => S_IFBLK,
+            FileType::NamedPipe => S_IFIFO,
+            FileType::Socket => S_IFSOCK,
+        };
+        Stat {
+            dev: info.dev as u64,
+            ino: info.inode as u64,
+            mode: type_ | info.mode as u32,
+            nlink: info.nlinks as u32,
+            uid: info.uid as u32,
+            gid: info.gid as u32,
+            size: info.size as u64,
+            blksize: info.blk_size as u32,
+            blocks: info.blocks as u64,
+            atime: info.atime.sec as u64,
+            mtime: info.mtime.sec as u64,
+            ctime: info.ctime.sec as u64,
+        }
+    }
+}
+
+// 将文件系统的错误转换为负的错误码
+pub fn fs_errno(err: FsError) -> isize {
+    -match err {
+        FsError::NotSupported => ENOSYS,
+        FsError::NotFile => EISDIR,
+        FsError::IsDir => EISDIR,
+        FsError::NotDir => ENOTDIR,
+        FsError::EntryNotFound => ENOENT,
+        FsError::EntryExist => EEXIST,
+        FsError::NotSameFs => EXDEV,
+        FsError::InvalidParam => EINVAL,
+        FsError::NoDeviceSpace => ENOSPC,
+        FsError::DirRemoved => ENOENT,
+        FsError::DirNotEmpty => ENOTEMPTY,
+        FsError::WrongFs => EINVAL,
+        FsError::DeviceError => EIO,
+        FsError::BrokenPipe => EPIPE,
+        FsError::Interrupted => EINTR,
+    }
+}
+
+fn current() -> Arc<Process> {
+    process::current_process().expect("kernel thread has no file")
+}
+
+// 文件的锁可能被阻塞读写的线程长时间持有，等待期间进程被结束时返回 -EINTR
+pub fn lock_file(file: &SleepMutex<File>) -> Result<MutexGuard<File>, isize> {
+    file.lock_killable().ok_or(-EINTR)
+}
+
+// 在 dir 下查找 path，O_CREAT 时文件不存在则在其所在目录中创建
+fn open_inode(dir: Arc<INode>, path: &str, flags: usize) -> Result<Arc<INode>, FsError> {
+    if flags & O_CREAT == 0 {
+        return dir.lookup(path);
+    }
+    let (parent, name) = match path.rfind('/') {
+        Some(pos) => (dir.lookup(&path[..pos + 1])?, &path[pos + 1..]),
+        None => (dir, path),
+    };
+    match parent.find(name) {
+        Ok(inode) => Ok(inode),
+        Err(FsError::EntryNotFound) => parent.create(name, FileType::File, 0o644),
+        Err(err) => Err(err),
+    }
+}
+
+// 目前只支持相对于根目录（AT_FDCWD）或某个已打开目录的路径
+pub fn sys_openat(dirfd: isize, path: UserCStr, flags: usize) -> isize {
+    let proc = current();
+    let path = match path.read() {
+        Ok(path) => path,
+        Err(err) => return err,
+    };
+    let dir = if dirfd == AT_FDCWD {
+        ROOT_INODE.clone()
+    } else {
+        match proc.get_file(dirfd as usize) {
+            Some(file) => match lock_file(&file) {
+                Ok(file) => file.inode(),
+                Err(err) => return err,
+            },
+            None => return -EBADF,
+        }
+    };
+    let inode = match open_inode(dir, &path, flags) {
+        Ok(inode) => inode,
+        Err(err) => return fs_errno(err),
+    };
+    let (readable, writable) = match flags & 0b11 {
+        O_RDONLY => (true, false),
+        O_WRONLY => (false, true),
+        O_RDWR => (true, true),
+        _ => return -EINVAL,
+    };
+    if flags & O_TRUNC != 0 && writable {
+        if let Err(err) = inode.resize(0) {
+            return fs_errno(err);
+        }
+    }
+    proc.add_file(File::new(inode, readable, writable)) as isize
+}
+
+pub fn sys_close(fd: usize) -> isize {
+    match current().remove_file(fd) {
+        Some(_) => 0,
+        None => -EBADF,
+    }
+}
+
+// 创建管道，fds[0] 为读端，fds[1] 为写端。目前不支持任何 flags
+pub fn sys_pipe2(fds: UserPtr<[i32; 2]>, flags: usize) -> isize {
+    if flags != 0 {
+        return -EINVAL;
+    }
+    let proc = current();
+    let (read, write) = make_pipe();
+    let read_fd = proc.add_file(File::new(read, true, false));
+    let write_fd = proc.add_file(File::new(write, false, true));
+    match fds.write([read_fd as i32, write_fd as i32]) {
+        Ok(()) => 0,
+        Err(err) => {
+            proc.remove_file(read_fd);
+            proc.remove_file(write_fd);
+            err
+        },
+    }
+}
+
+// 让 newfd 指向 oldfd 打开的文件，两者共享读写位置。newfd 原来打开的文件会被关闭
+pub fn sys_dup3(oldfd: usize, newfd: usize, flags: usize) -> isize {
+    if oldfd == newfd || flags != 0 {
+        return -EINVAL;
+    }
+    let proc = current();
+    let mut files = proc.files.lock();
+    let file = match files.get(&oldfd) {
+        Some(file) => file.clone(),
+        None => return -EBADF,
+    };
+    files.insert(newfd, file);
+    newfd as isize
+}
+
+// 先读到内核的缓冲区中，再复制给用户。每次最多读 IO_BUF_SIZE 字节，不会按用户给出的长度分配内核内存
+// 只有普通文件会读满整个数组，管道和设备读到一部分数据就返回，避免在已经读到数据时继续阻塞
+pub fn sys_read(fd: usize, base: UserSlice) -> isize {
+    let file = match current().get_file(fd) {
+        Some(file) => file,
+        None => return -EBADF,
+    };
+    // 在阻塞读取之前检查地址，避免读到的数据无处可放
+    if let Err(err) = base.check(true) {
+        return err;
+    }
+    let mut file = match lock_file(&file) {
+        Ok(file) => file,
+        Err(err) => return err,
+    };
+    let regular = file.info().map_or(false, |info| info.type_ == FileType::File);
+    let mut buf = [0u8; IO_BUF_SIZE];
+    let mut total = 0;
+    while total < base.len() {
+        let len = IO_BUF_SIZE.min(base.len() - total);
+        let read = match file.read(&mut buf[..len]) {
+            Ok(read) => read,
+            Err(_) if total > 0 => break,
+            Err(err) => return fs_errno(err),
+        };
+        if let Err(err) = base.sub(total, read).write(&buf[..read]) {
+            return err;
+        }
+        total += read;
+        if read < len || !regular {
+            break;
+        }
+    }
+    total as isize
+}
+
+// 每次从用户复制最多 IO_BUF_SIZE 字节再写入文件
+pub fn sys_write(fd: usize, base: UserSlice) -> isize {
+    let file = match current().get_file(fd) {
+        Some(file) => file,
+        None => return -EBADF,
+    };
+    if let Err(err) = base.check(false) {
+        return err;
+    }
+    let mut file = match lock_file(&file) {
+        Ok(file) => file,
+        Err(err) => return err,
+    };
+    let mut total = 0;
+    while total < base.len() {
+        let len = IO_BUF_SIZE.min(base.len() - total);
+        let buf = match base.sub(total, len).read() {
+            Ok(buf) => buf,
+            Err(err) => return err,
+        };
+        let written = match file.write(&buf) {
+            Ok(written) => written,
+            Err(_) if total > 0 => break,
+            Err(err) => return fs_errno(err),
+        };
+        total += written;
+        if written < len {
+            break;
+        }
+    }
+    total as isize
+}
+
+pub fn sys_lseek(fd: usize, offset: isize, whence: u8) -> isize {
+    let file = match current().get_file(fd) {
+        Some(file) => file,
+        None => return -EBADF,
+    };
+    let pos = match whence {
+        SEEK_SET => SeekFrom::Start(offset as u64),
+        SEEK_CUR => SeekFrom::Current(offset as i64),
+        SEEK_END => SeekFrom::End(offset as i64),
+        _ => return -EINVAL,
+    };
+    let mut file = match lock_file(&file) {
+        Ok(file) => file,
+        Err(err) => return err,
+    };
+    // 字符设备和管道不能移动读写位置
+    match file.info() {
+        Ok(ref info) if info.type_ == FileType::CharDevice || info.type_ == FileType::NamedPipe => return -ESPIPE,
+        _ => {},
+    }
+    match file.seek(pos) {
+        Ok(offset) => offset as isize,
+        Err(err) => fs_errno(err),
+    }
+}
+
+pub fn sys_fstat(fd: usize, stat: UserPtr<Stat>) -> isize {
+    let file = match current().get_file(fd) {
+        Some(file) => file,
+        None => return -EBADF,
+    };
+    let info = match lock_file(&file) {
+        Ok(file) => file.info(),
+        Err(err) => return err,
+    };
+    match info {
+        Ok(info) => match stat.write(Stat::from(info)) {
+            Ok(()) => 0,
+            Err(err) => err,
+        },
+        Err(err) => fs_errno(err),
+    }
+}
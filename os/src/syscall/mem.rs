@@ -0,0 +1,260 @@
+use alloc::sync::Arc;
+use crate::consts::*;
+use crate::memory::shm::{ self, IPC_PRIVATE };
+use crate::memory_set::{ attr::MemoryAttr, handler::{ ByFile, ByFrameLazy, ByShm } };
+use crate::process;
+use super::*;
+
+pub const PROT_NONE: usize = 0;
+pub const PROT_READ: usize = 1;
+pub const PROT_WRITE: usize = 2;
+pub const PROT_EXEC: usize = 4;
+
+pub const MAP_SHARED: usize = 0x01;
+pub const MAP_PRIVATE: usize = 0x02;
+pub const MAP_FIXED: usize = 0x10;
+pub const MAP_ANONYMOUS: usize = 0x20;
+
+pub const IPC_CREAT: usize = 0o1000;
+pub const IPC_EXCL: usize = 0o2000;
+pub const SHM_RDONLY: usize = 0o10000;
+
+// 共享内存段的最大大小
+const SHM_MAX_SIZE: usize = 0x100_0000;
+
+// 向上按页对齐，溢出时返回 None
+fn page_align_up(addr: usize) -> Option<usize> {
+    Some(addr.checked_add(PAGE_SIZE - 1)? & !(PAGE_SIZE - 1))
+}
+
+// PROT_NONE 的区域不允许用户访问
+fn prot_to_attr(prot: usize) -> MemoryAttr {
+    let mut attr = MemoryAttr::new();
+    if prot & (PROT_READ | PROT_WRITE | PROT_EXEC) != 0 {
+        attr = attr.set_user();
+    }
+    if prot & PROT_WRITE == 0 {
+        attr = attr.set_readonly();
+    }
+    if prot & PROT_EXEC != 0 {
+        attr = attr.set_execute();
+    }
+    attr
+}
+
+// 检查 [addr, addr + len) 是否按页对齐且位于用户地址空间中，返回按页对齐的末地址
+fn user_range(addr: usize, len: usize) -> Option<usize> {
+    if addr & (PAGE_SIZE - 1) != 0 || len == 0 {
+        return None;
+    }
+    let end = page_align_up(addr.checked_add(len)?)?;
+    if end > USER_STACK_OFFSET + USER_STACK_SIZE {
+        return None;
+    }
+    Some(end)
+}
+
+// 设置 brk，返回新的 brk。addr 不合法或无法分配时不做修改，返回原来的值
+pub fn sys_brk(addr: usize) -> isize {
+    let proc = process::current_process().expect("kernel thread has no heap");
+    let mut heap = proc.heap.lock();
+    if addr < heap.start || addr > USER_MMAP_OFFSET {
+        return heap.end as isize;
+    }
+    let (old_top, new_top) = match (page_align_up(heap.end), page_align_up(addr)) {
+        (Some(old_top), Some(new_top)) => (old_top, new_top),
+        _ => return heap.end as isize,
+    };
+    let mut vm = proc.vm.lock();
+    if new_top > old_top {
+        if !vm.test_free_area(old_top, new_top) {
+            return heap.end as isize;
+        }
+        vm.push(old_top, new_top, MemoryAttr::new().set_user(), ByFrameLazy::new());
+    } else if new_top < old_top {
+        vm.remove(new_top, old_top);
+    }
+    heap.end = addr;
+    addr as isize
+}
+
+// 匿名映射只支持私有映射。页帧都在第一次访问时才分配
+pub fn sys_mmap(addr: usize, len: usize, prot: usize, flags: usize, fd: usize, offset: usize) -> isize {
+    let shared = match flags & (MAP_SHARED | MAP_PRIVATE) {
+        MAP_SHARED => true,
+        MAP_PRIVATE => false,
+        _ => return -EINVAL,
+    };
+    if len == 0 || offset & (PAGE_SIZE - 1) != 0 || (shared && flags & MAP_ANONYMOUS != 0) {
+        return -EINVAL;
+    }
+    let len = match page_align_up(len) {
+        Some(len) => len,
+        None => return -ENOMEM,
+    };
+    // 映射范围内的文件偏移不能溢出
+    if offset.checked_add(len).is_none() {
+        return -EINVAL;
+    }
+    let proc = process::current_process().expect("kernel thread can not mmap");
+    let inode = if flags & MAP_ANONYMOUS != 0 {
+        None
+    } else {
+        let file = match proc.get_file(fd) {
+            Some(file) => file,
+            None => return -EBADF,
+        };
+        let file = match super::fs::lock_file(&file) {
+            Ok(file) => file,
+            Err(err) => return err,
+        };
+        // 共享的可写映射会写回文件，需要文件可写
+        if !file.readable() || (shared && prot & PROT_WRITE != 0 && !file.writable()) {
+            return -EACCES;
+        }
+        Some((file.inode(), file.writable()))
+    };
+    let mut vm = proc.vm.lock();
+    let start = if flags & MAP_FIXED != 0 {
+        if user_range(addr, len).is_none() {
+            return -EINVAL;
+        }
+        vm.remove(addr, addr + len);
+        addr
+    } else {
+        match vm.find_free_area(USER_MMAP_OFFSET, USER_STACK_OFFSET, len) {
+            Some(start) => start,
+            None => return -ENOMEM,
+        }
+    };
+    match inode {
+        Some((inode, writable)) => vm.push(start, start + len, prot_to_attr(prot), ByFile::new(inode, start, offset, usize::max_value(), shared, writable)),
+        None => vm.push(start, start + len, prot_to_attr(prot), ByFrameLazy::new()),
+    }
+    start as isize
+}
+
+// 把共享文件映射的修改写回文件
+pub fn sys_msync(addr: usize, len: usize, _flags: usize) -> isize {
+    let end = match user_range(addr, len) {
+        Some(end) => end,
+        None => return -EINVAL,
+    };
+    let proc = process::current_process().expect("kernel thread can not msync");
+    let vm = proc.vm.lock();
+    if !vm.check_user_range(addr, end, false) {
+        return -ENOMEM;
+    }
+    vm.sync(addr, end);
+    0
+}
+
+// 共享文件映射的修改在取消映射前写回文件
+pub fn sys_munmap(addr: usize, len: usize) -> isize {
+    let end = match user_range(addr, len) {
+        Some(end) => end,
+        None => return -EINVAL,
+    };
+    let proc = process::current_process().expect("kernel thread can not munmap");
+    proc.vm.lock().remove(addr, end);
+    0
+}
+
+pub fn sys_mprotect(addr: usize, len: usize, prot: usize) -> isize {
+    let end = match user_range(addr, len) {
+        Some(end) => end,
+        None => return -EINVAL,
+    };
+    let proc = process::current_process().expect("kernel thread can not mprotect");
+    let mut vm = proc.vm.lock();
+    // 与 mmap 相同，只读打开的文件的共享映射以及只读挂载的共享内存段不能改为可写
+    if prot & PROT_WRITE != 0 && !vm.can_write(addr, end) {
+        return -EACCES;
+    }
+    let ok = vm.protect(addr, end, prot_to_attr(prot));
+    if ok { 0 } else { -ENOMEM }
+}
+
+// 取得键为 key 的共享内存段，返回其在本进程中的编号。key 为 IPC_PRIVATE 时总是创建新的段
+// 已经存在的段的大小不能小于 size，size 为 0 表示不限制
+pub fn sys_shmget(key: usize, size: usize, flags: usize) -> isize {
+    let segment = match shm::find(key) {
+        Some(_) if flags & IPC_CREAT != 0 && flags & IPC_EXCL != 0 => return -EEXIST,
+        Some(segment) => {
+            if size > segment.size() {
+                return -EINVAL;
+            }
+            segment
+        },
+        None => {
+            if key != IPC_PRIVATE && flags & IPC_CREAT == 0 {
+                return -ENOENT;
+            }
+            let size = match page_align_up(size) {
+                Some(size) if size != 0 && size <= SHM_MAX_SIZE => size,
+                _ => return -EINVAL,
+            };
+            match shm::create(key, size) {
+                Some(segment) => segment,
+                None => return -EEXIST,
+            }
+        },
+    };
+    let proc = process::current_process().expect("kernel thread can not get shared memory");
+    let mut shms = proc.shms.lock();
+    // 同一个段在一个进程中只占用一个编号，失效的编号可以重新使用
+    if let Some(id) = shms.iter().position(|other| other.as_ref().map_or(false, |other| Arc::ptr_eq(other, &segment))) {
+        return id as isize;
+    }
+    if let Some(id) = shms.iter().position(Option::is_none) {
+        shms[id] = Some(segment);
+        return id as isize;
+    }
+    shms.push(Some(segment));
+    (shms.len() - 1) as isize
+}
+
+// 把编号为 id 的共享内存段挂载到 addr 处，addr 为 0 时由内核选择地址。返回挂载的起始地址
+pub fn sys_shmat(id: usize, addr: usize, flags: usize) -> isize {
+    let proc = process::current_process().expect("kernel thread can not attach shared memory");
+    let segment = match proc.shms.lock().get(id) {
+        Some(Some(segment)) => segment.clone(),
+        _ => return -EINVAL,
+    };
+    let len = segment.size();
+    let mut vm = proc.vm.lock();
+    let start = if addr != 0 {
+        if user_range(addr, len).is_none() || !vm.test_free_area(addr, addr + len) {
+            return -EINVAL;
+        }
+        addr
+    } else {
+        match vm.find_free_area(USER_MMAP_OFFSET, USER_STACK_OFFSET, len) {
+            Some(start) => start,
+            None => return -ENOMEM,
+        }
+    };
+    let writable = flags & SHM_RDONLY == 0;
+    let prot = if writable { PROT_READ | PROT_WRITE } else { PROT_READ };
+    vm.push(start, start + len, prot_to_attr(prot), ByShm::new(segment, start, writable));
+    start as isize
+}
+
+// 取消挂载在 addr 处的共享内存段。本进程不再挂载这个段时其编号随之失效，
+// 这样最后一个挂载取消后段就会被释放
+pub fn sys_shmdt(addr: usize) -> isize {
+    let proc = process::current_process().expect("kernel thread can not detach shared memory");
+    let mut vm = proc.vm.lock();
+    let segment = match vm.detach(addr) {
+        Some(segment) => segment,
+        None => return -EINVAL,
+    };
+    if !vm.is_attached(&segment) {
+        for slot in proc.shms.lock().iter_mut() {
+            if slot.as_ref().map_or(false, |other| Arc::ptr_eq(other, &segment)) {
+                *slot = None;
+            }
+        }
+    }
+    0
+}
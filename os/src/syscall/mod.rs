@@ -0,0 +1,301 @@
+use crate::context::TrapFrame;
+use crate::process;
+use crate::clock::TICKS_PER_SEC;
+
+mod fs;
+mod mem;
+mod sync;
+mod user;
+
+use self::fs::*;
+use self::mem::*;
+use self::sync::*;
+use self::user::*;
+
+pub const SYS_DUP3: usize = 24;
+pub const SYS_OPENAT: usize = 56;
+pub const SYS_CLOSE: usize = 57;
+pub const SYS_PIPE2: usize = 59;
+pub const SYS_LSEEK: usize = 62;
+pub const SYS_READ: usize = 63;
+pub const SYS_WRITE: usize = 64;
+pub const SYS_FSTAT: usize = 80;
+pub const SYS_EXIT: usize = 93;
+pub const SYS_FUTEX: usize = 98;
+pub const SYS_NANOSLEEP: usize = 101;
+pub const SYS_SETPRIORITY: usize = 140;
+pub const SYS_GETTID: usize = 178;
+pub const SYS_SHMGET: usize = 194;
+pub const SYS_SHMAT: usize = 196;
+pub const SYS_SHMDT: usize = 197;
+pub const SYS_BRK: usize = 214;
+pub const SYS_MUNMAP: usize = 215;
+pub const SYS_CLONE: usize = 220;
+pub const SYS_EXECVE: usize = 221;
+pub const SYS_MMAP: usize = 222;
+pub const SYS_MPROTECT: usize = 226;
+pub const SYS_MSYNC: usize = 227;
+pub const SYS_WAIT4: usize = 260;
+pub const SYS_THREAD_CREATE: usize = 1000;
+pub const SYS_THREAD_JOIN: usize = 1002;
+pub const SYS_MUTEX_CREATE: usize = 1010;
+pub const SYS_MUTEX_LOCK: usize = 1011;
+pub const SYS_MUTEX_UNLOCK: usize = 1012;
+pub const SYS_SEMAPHORE_CREATE: usize = 1020;
+pub const SYS_SEMAPHORE_UP: usize = 1021;
+pub const SYS_SEMAPHORE_DOWN: usize = 1022;
+
+// 出错时系统调用返回负的错误码
+pub const EPERM: isize = 1;
+pub const ENOENT: isize = 2;
+pub const ESRCH: isize = 3;
+pub const EINTR: isize = 4;
+pub const EIO: isize = 5;
+pub const E2BIG: isize = 7;
+pub const ENOEXEC: isize = 8;
+pub const EBADF: isize = 9;
+pub const ECHILD: isize = 10;
+pub const EAGAIN: isize = 11;
+pub const ENOMEM: isize = 12;
+pub const EACCES: isize = 13;
+pub const EFAULT: isize = 14;
+pub const EBUSY: isize = 16;
+pub const EEXIST: isize = 17;
+pub const EXDEV: isize = 18;
+pub const ENOTDIR: isize = 20;
+pub const EISDIR: isize = 21;
+pub const EINVAL: isize = 22;
+pub const ENOSPC: isize = 28;
+pub const ESPIPE: isize = 29;
+pub const EPIPE: isize = 32;
+pub const ENOSYS: isize = 38;
+pub const ENOTEMPTY: isize = 39;
+pub const ETIMEDOUT: isize = 110;
+
+pub fn syscall(id: usize, args: [usize;6], tf: &mut TrapFrame) -> isize {
+    match id {
+        SYS_DUP3 => {
+            return sys_dup3(args[0], args[1], args[2]);
+        },
+        SYS_OPENAT => {
+            return sys_openat(args[0] as isize, UserCStr::new(args[1]), args[2]);
+        },
+        SYS_CLOSE => {
+            return sys_close(args[0]);
+        },
+        SYS_PIPE2 => {
+            return sys_pipe2(UserPtr::new(args[0]), args[1]);
+        },
+        SYS_LSEEK => {
+            return sys_lseek(args[0], args[1] as isize, args[2] as u8);
+        },
+        SYS_READ => {
+            return sys_read(args[0], UserSlice::new(args[1], args[2]));
+        },
+        SYS_WRITE => {
+            return sys_write(args[0], UserSlice::new(args[1], args[2]));
+        },
+        SYS_FSTAT => {
+            return sys_fstat(args[0], UserPtr::new(args[1]));
+        },
+        SYS_EXIT => {
+            sys_exit(args[0]);
+        },
+        SYS_FUTEX => {
+            return sys_futex(UserPtr::new(args[0]), args[1], args[2], UserPtr::new(args[3]));
+        },
+        SYS_NANOSLEEP => {
+            return sys_nanosleep(UserPtr::new(args[0]));
+        },
+        SYS_SETPRIORITY => {
+            return sys_set_priority(args[0]);
+        },
+        SYS_GETTID => {
+            return sys_gettid();
+        },
+        SYS_SHMGET => {
+            return sys_shmget(args[0], args[1], args[2]);
+        },
+        SYS_SHMAT => {
+            return sys_shmat(args[0], args[1], args[2]);
+        },
+        SYS_SHMDT => {
+            return sys_shmdt(args[0]);
+        },
+        SYS_BRK => {
+            return sys_brk(args[0]);
+        },
+        SYS_MUNMAP => {
+            return sys_munmap(args[0], args[1]);
+        },
+        SYS_CLONE => {
+            return sys_clone(args[0], args[1], tf);
+        },
+        SYS_EXECVE => {
+            return sys_execve(UserCStr::new(args[0]), UserPtr::new(args[1]), UserPtr::new(args[2]), tf);
+        },
+        SYS_MMAP => {
+            return sys_mmap(args[0], args[1], args[2], args[3], args[4], args[5]);
+        },
+        SYS_MPROTECT => {
+            return sys_mprotect(args[0], args[1], args[2]);
+        },
+        SYS_MSYNC => {
+            return sys_msync(args[0], args[1], args[2]);
+        },
+        SYS_WAIT4 => {
+            return sys_wait(args[0] as isize, UserPtr::new(args[1]));
+        },
+        SYS_THREAD_CREATE => {
+            return sys_thread_create(args[0], args[1], args[2]);
+        },
+        SYS_THREAD_JOIN => {
+            return sys_thread_join(args[0], UserPtr::new(args[1]));
+        },
+        SYS_MUTEX_CREATE => {
+            return sys_mutex_create();
+        },
+        SYS_MUTEX_LOCK => {
+            return sys_mutex_lock(args[0]);
+        },
+        SYS_MUTEX_UNLOCK => {
+            return sys_mutex_unlock(args[0]);
+        },
+        SYS_SEMAPHORE_CREATE => {
+            return sys_semaphore_create(args[0]);
+        },
+        SYS_SEMAPHORE_UP => {
+            return sys_semaphore_up(args[0]);
+        },
+        SYS_SEMAPHORE_DOWN => {
+            return sys_semaphore_down(args[0]);
+        },
+        _ => {
+            println!("unknown syscall id {}", id);
+            return -ENOSYS;
+        },
+    };
+    return 0;
+}
+
+fn sys_exit(code: usize) {
+    process::exit(code);
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct TimeSpec {
+    sec: usize,
+    nsec: usize,
+}
+
+impl TimeSpec {
+    // 换算成时钟周期数，向上取整，太大时取 usize 的最大值。nsec 不合法时返回 None
+    fn to_ticks(&self) -> Option<usize> {
+        if self.nsec >= 1_000_000_000 {
+            return None;
+        }
+        const NSEC_PER_TICK: usize = 1_000_000_000 / TICKS_PER_SEC;
+        Some(self.sec.saturating_mul(TICKS_PER_SEC).saturating_add((self.nsec + NSEC_PER_TICK - 1) / NSEC_PER_TICK))
+    }
+}
+
+fn sys_nanosleep(req: UserPtr<TimeSpec>) -> isize {
+    let req = match req.read() {
+        Ok(req) => req,
+        Err(err) => return err,
+    };
+    match req.to_ticks() {
+        Some(ticks) => process::sleep(ticks),
+        None => return -EINVAL,
+    }
+    0
+}
+
+fn sys_set_priority(priority: usize) -> isize {
+    process::set_priority(priority);
+    0
+}
+
+pub const CLONE_VM: usize = 0x100;
+
+// 带有 CLONE_VM 时在当前进程中创建一个新线程，在 newsp 指向的栈上返回 0，否则与 fork 相同
+// 返回新线程的 tid 或新进程的 pid
+fn sys_clone(flags: usize, newsp: usize, tf: &mut TrapFrame) -> isize {
+    if flags & CLONE_VM != 0 {
+        process::clone_thread(tf, newsp) as isize
+    } else {
+        process::fork(tf) as isize
+    }
+}
+
+fn sys_gettid() -> isize {
+    process::current_tid() as isize
+}
+
+// 创建一个从 entry 开始执行的线程，栈顶为 stack，arg 通过 a0 传入
+fn sys_thread_create(entry: usize, stack: usize, arg: usize) -> isize {
+    if let Err(err) = UserSlice::new(stack.wrapping_sub(4), 4).check(true) {
+        return err;
+    }
+    process::thread_create(entry, stack, arg) as isize
+}
+
+// 等待当前进程中的线程 tid 退出，退出码写入 code 指向的位置
+fn sys_thread_join(tid: usize, code: UserPtr<i32>) -> isize {
+    if tid == process::current_tid() {
+        return -EINVAL;
+    }
+    match process::thread_join(tid) {
+        Some(exit_code) => {
+            if !code.is_null() {
+                if let Err(err) = code.write(exit_code as i32) {
+                    return err;
+                }
+            }
+            0
+        },
+        None => -ESRCH,
+    }
+}
+
+// 参数和环境变量的总长度上限
+const ARG_MAX: usize = 0x4000;
+
+fn sys_execve(path : UserCStr, argv : UserPtr<usize>, envp : UserPtr<usize>, tf : &mut TrapFrame) -> isize {
+    // 新地址空间建立后旧的地址空间就被释放了，需要先把参数复制到内核中
+    let path = match path.read() {
+        Ok(path) => path,
+        Err(err) => return err,
+    };
+    let args = match read_cstr_array(argv, ARG_MAX) {
+        Ok(args) => args,
+        Err(err) => return err,
+    };
+    let envs = match read_cstr_array(envp, ARG_MAX) {
+        Ok(envs) => envs,
+        Err(err) => return err,
+    };
+    match process::execve(&path, args, envs, tf) {
+        Ok(argc) => argc as isize,
+        Err(process::ExecError::Fs(err)) => fs_errno(err),
+        Err(process::ExecError::NotExecutable) => -ENOEXEC,
+        Err(process::ExecError::MultiThreaded) => -EBUSY,
+    }
+}
+
+// pid 为 -1 时等待任意子进程，退出码写入 status 指向的位置
+fn sys_wait(pid: isize, status: UserPtr<i32>) -> isize {
+    let target = if pid == -1 { None } else { Some(pid as usize) };
+    match process::wait(target) {
+        Some((pid, code)) => {
+            if !status.is_null() {
+                if let Err(err) = status.write(code as i32) {
+                    return err;
+                }
+            }
+            pid as isize
+        },
+        None => -ECHILD,
+    }
+}
@@ -0,0 +1,130 @@
+use alloc::sync::Arc;
+use crate::consts::PAGE_SIZE;
+use crate::interrupt::{ disable_and_store, restore };
+use crate::memory::paging::active_table;
+use crate::process;
+use crate::sync::{ futex, mutex::UserMutex, semaphore::Semaphore };
+use super::*;
+
+// 创建一个互斥锁，返回其编号
+pub fn sys_mutex_create() -> isize {
+    let proc = process::current_process().expect("kernel thread can not create mutex");
+    let mut mutexes = proc.mutexes.lock();
+    mutexes.push(Arc::new(UserMutex::new()));
+    (mutexes.len() - 1) as isize
+}
+
+// 等待期间不能持有进程的互斥锁表，否则其他线程无法解锁
+pub fn sys_mutex_lock(id: usize) -> isize {
+    let proc = process::current_process().expect("kernel thread can not lock mutex");
+    let mutex = match proc.mutexes.lock().get(id) {
+        Some(mutex) => mutex.clone(),
+        None => return -EINVAL,
+    };
+    // 锁会一直持有到 sys_mutex_unlock
+    if mutex.lock(process::current_tid()) { 0 } else { -EINTR }
+}
+
+pub fn sys_mutex_unlock(id: usize) -> isize {
+    let proc = process::current_process().expect("kernel thread can not unlock mutex");
+    let mutex = match proc.mutexes.lock().get(id) {
+        Some(mutex) => mutex.clone(),
+        None => return -EINVAL,
+    };
+    // 没有被锁住时返回 EINVAL，被其它线程持有时返回 EPERM
+    if mutex.owner().is_none() {
+        return -EINVAL;
+    }
+    if !mutex.unlock(process::current_tid()) {
+        return -EPERM;
+    }
+    0
+}
+
+// 创建一个初始计数为 count 的信号量，返回其编号
+pub fn sys_semaphore_create(count: usize) -> isize {
+    let proc = process::current_process().expect("kernel thread can not create semaphore");
+    let mut semaphores = proc.semaphores.lock();
+    semaphores.push(Arc::new(Semaphore::new(count)));
+    (semaphores.len() - 1) as isize
+}
+
+pub fn sys_semaphore_up(id: usize) -> isize {
+    let proc = process::current_process().expect("kernel thread can not use semaphore");
+    let sem = match proc.semaphores.lock().get(id) {
+        Some(sem) => sem.clone(),
+        None => return -EINVAL,
+    };
+    sem.release();
+    0
+}
+
+pub fn sys_semaphore_down(id: usize) -> isize {
+    let proc = process::current_process().expect("kernel thread can not use semaphore");
+    let sem = match proc.semaphores.lock().get(id) {
+        Some(sem) => sem.clone(),
+        None => return -EINVAL,
+    };
+    if sem.acquire_killable() { 0 } else { -EINTR }
+}
+
+pub const FUTEX_WAIT: usize = 0;
+pub const FUTEX_WAKE: usize = 1;
+pub const FUTEX_PRIVATE_FLAG: usize = 128;
+
+// 以用户内存字的物理地址作为 futex 的键，同时返回它当前的值
+// 先写回原值，让写时复制的页完成复制，否则等待者和唤醒者可能看到不同的物理页
+fn futex_key(uaddr: &UserPtr<i32>) -> Result<(usize, i32), isize> {
+    if uaddr.addr() % 4 != 0 {
+        return Err(-EINVAL);
+    }
+    let value = uaddr.read()?;
+    uaddr.write(value)?;
+    let paddr = active_table()
+        .get_entry(uaddr.addr())
+        .filter(|entry| entry.present())
+        .map(|entry| entry.target() + uaddr.addr() % PAGE_SIZE)
+        .ok_or(-EFAULT)?;
+    Ok((paddr, value))
+}
+
+// FUTEX_WAIT：uaddr 处的值等于 val 时睡眠，timeout 不为空时最多等待这么长时间
+// FUTEX_WAKE：唤醒至多 val 个在 uaddr 上等待的线程，返回唤醒的数量
+pub fn sys_futex(uaddr: UserPtr<i32>, op: usize, val: usize, timeout: UserPtr<TimeSpec>) -> isize {
+    match op & !FUTEX_PRIVATE_FLAG {
+        FUTEX_WAIT => {
+            let ticks = if timeout.is_null() {
+                None
+            } else {
+                match timeout.read().map(|timeout| timeout.to_ticks()) {
+                    Ok(Some(ticks)) => Some(ticks),
+                    Ok(None) => return -EINVAL,
+                    Err(err) => return err,
+                }
+            };
+            // 从检查值到进入等待队列之间不能被打断，否则可能错过唤醒
+            let flags = disable_and_store();
+            let (key, value) = match futex_key(&uaddr) {
+                Ok(key) => key,
+                Err(err) => {
+                    restore(flags);
+                    return err;
+                },
+            };
+            if value != val as i32 {
+                restore(flags);
+                return -EAGAIN;
+            }
+            let woken = futex::wait(key, ticks);
+            restore(flags);
+            if woken { 0 } else { -ETIMEDOUT }
+        },
+        FUTEX_WAKE => {
+            match futex_key(&uaddr) {
+                Ok((key, _)) => futex::wake(key, val) as isize,
+                Err(err) => err,
+            }
+        },
+        _ => -ENOSYS,
+    }
+}
@@ -0,0 +1,165 @@
+use alloc::{ string::String, vec::Vec };
+use core::marker::PhantomData;
+use core::mem::{ self, size_of };
+use core::slice;
+use crate::consts::PAGE_SIZE;
+use crate::memory::copy_user;
+use crate::process;
+use super::{ E2BIG, EFAULT, EINVAL };
+
+// 检查 [addr, addr + len) 是否都在当前进程用户可访问的区域内，write 表示是否需要写权限
+fn check_range(addr: usize, len: usize, write: bool) -> Result<(), isize> {
+    let end = match addr.checked_add(len) {
+        Some(end) => end,
+        None => return Err(-EFAULT),
+    };
+    let proc = process::current_process().ok_or(-EFAULT)?;
+    let ok = proc.vm.lock().check_user_range(addr, end, write);
+    if ok { Ok(()) } else { Err(-EFAULT) }
+}
+
+// 检查之后其它线程仍可能解除映射，复制时出错同样返回 EFAULT
+fn copy_from_user(dst: &mut [u8], src: usize) -> Result<(), isize> {
+    check_range(src, dst.len(), false)?;
+    if copy_user(dst.as_mut_ptr(), src as *const u8, dst.len()) { Ok(()) } else { Err(-EFAULT) }
+}
+
+fn copy_to_user(dst: usize, src: &[u8]) -> Result<(), isize> {
+    check_range(dst, src.len(), true)?;
+    if copy_user(dst as *mut u8, src.as_ptr(), src.len()) { Ok(()) } else { Err(-EFAULT) }
+}
+
+// 指向用户地址空间中一个 T 的指针，读写前检查地址是否合法
+pub struct UserPtr<T> {
+    addr: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Copy> UserPtr<T> {
+    pub fn new(addr: usize) -> Self {
+        UserPtr { addr, _marker: PhantomData }
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.addr == 0
+    }
+
+    pub fn addr(&self) -> usize {
+        self.addr
+    }
+
+    // 指向其后第 count 个元素
+    pub fn add(&self, count: usize) -> Self {
+        UserPtr::new(self.addr.wrapping_add(count * size_of::<T>()))
+    }
+
+    pub fn read(&self) -> Result<T, isize> {
+        let mut value: T = unsafe { mem::zeroed() };
+        let bytes = unsafe { slice::from_raw_parts_mut(&mut value as *mut T as *mut u8, size_of::<T>()) };
+        copy_from_user(bytes, self.addr)?;
+        Ok(value)
+    }
+
+    pub fn write(&self, value: T) -> Result<(), isize> {
+        let bytes = unsafe { slice::from_raw_parts(&value as *const T as *const u8, size_of::<T>()) };
+        copy_to_user(self.addr, bytes)
+    }
+}
+
+// 用户地址空间中的一段字节数组
+pub struct UserSlice {
+    addr: usize,
+    len: usize,
+}
+
+impl UserSlice {
+    pub fn new(addr: usize, len: usize) -> Self {
+        UserSlice { addr, len }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn check(&self, write: bool) -> Result<(), isize> {
+        check_range(self.addr, self.len, write)
+    }
+
+    // 从 offset 开始长度为 len 的一段，不能超出这段数组
+    pub fn sub(&self, offset: usize, len: usize) -> UserSlice {
+        assert!(offset <= self.len && len <= self.len - offset);
+        UserSlice { addr: self.addr + offset, len }
+    }
+
+    // 复制到内核中
+    pub fn read(&self) -> Result<Vec<u8>, isize> {
+        let mut buf = Vec::new();
+        buf.resize(self.len, 0);
+        copy_from_user(&mut buf, self.addr)?;
+        Ok(buf)
+    }
+
+    // 把 data 写到这段数组的开头，data 不能比它长
+    pub fn write(&self, data: &[u8]) -> Result<(), isize> {
+        assert!(data.len() <= self.len);
+        copy_to_user(self.addr, data)
+    }
+}
+
+// 用户地址空间中以 '\0' 结尾的字符串
+pub struct UserCStr {
+    addr: usize,
+}
+
+// 字符串的最大长度
+const MAX_CSTR_LEN: usize = 0x1000;
+
+impl UserCStr {
+    pub fn new(addr: usize) -> Self {
+        UserCStr { addr }
+    }
+
+    // 逐页复制，直到遇到 '\0'。不是合法的 UTF-8 编码或过长时返回 EINVAL
+    pub fn read(&self) -> Result<String, isize> {
+        let mut bytes = Vec::new();
+        let mut addr = self.addr;
+        let mut page = [0u8; PAGE_SIZE];
+        loop {
+            let len = PAGE_SIZE - addr % PAGE_SIZE;
+            copy_from_user(&mut page[..len], addr)?;
+            if let Some(pos) = page[..len].iter().position(|&c| c == 0) {
+                bytes.extend_from_slice(&page[..pos]);
+                break;
+            }
+            bytes.extend_from_slice(&page[..len]);
+            addr += len;
+            if bytes.len() > MAX_CSTR_LEN {
+                return Err(-EINVAL);
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| -EINVAL)
+    }
+}
+
+// 读取以空指针结尾的字符串指针数组，array 为空指针时返回空数组
+// 字符串的总长度超过 limit 时返回 E2BIG
+pub fn read_cstr_array(array: UserPtr<usize>, limit: usize) -> Result<Vec<String>, isize> {
+    let mut strings = Vec::new();
+    if array.is_null() {
+        return Ok(strings);
+    }
+    let mut total = 0;
+    for i in 0.. {
+        let s = array.add(i).read()?;
+        if s == 0 {
+            break;
+        }
+        let s = UserCStr::new(s).read()?;
+        total += s.len() + 1 + size_of::<usize>();
+        if total > limit {
+            return Err(-E2BIG);
+        }
+        strings.push(s);
+    }
+    Ok(strings)
+}
@@ -0,0 +1,101 @@
+use alloc::{ boxed::Box, vec::Vec };
+use crate::sync::spinlock::IrqSpinLock;
+use lazy_static::*;
+use crate::process::{ self, Tid };
+
+// 时间轮：共 WHEEL_SIZE 个槽，截止时刻为 deadline 的定时器放在 deadline % WHEEL_SIZE 号槽中
+// 每个时钟周期只需检查一个槽，超过一圈的定时器留在槽中等下一圈
+const WHEEL_SIZE: usize = 64;
+
+pub enum TimerEvent {
+    Wakeup(Tid),    // 唤醒线程
+    Callback(Box<FnMut() + Send>),  // 在时钟中断中调用，只会调用一次
+}
+
+struct Timer {
+    id: usize,
+    deadline: usize,
+    event: TimerEvent,
+}
+
+pub struct TimerWheel {
+    slots: Vec<Vec<Timer>>,
+    current: usize,     // 已经处理到的时钟周期
+    next_id: usize,
+}
+
+impl TimerWheel {
+    pub fn new() -> Self {
+        let mut slots = Vec::new();
+        slots.resize_with(WHEEL_SIZE, Default::default);
+        TimerWheel {
+            slots,
+            current: 0,
+            next_id: 0,
+        }
+    }
+
+    // 添加一个定时器，返回其编号，可用于取消
+    pub fn add(&mut self, deadline: usize, event: TimerEvent) -> usize {
+        // 已经过期的定时器在下一个时钟周期触发
+        let deadline = deadline.max(self.current + 1);
+        let id = self.next_id;
+        self.next_id += 1;
+        self.slots[deadline % WHEEL_SIZE].push(Timer{ id, deadline, event });
+        id
+    }
+
+    // 取消定时器，返回它是否还未触发
+    pub fn cancel(&mut self, id: usize) -> bool {
+        for slot in self.slots.iter_mut() {
+            if let Some(idx) = slot.iter().position(|timer| timer.id == id) {
+                slot.remove(idx);
+                return true;
+            }
+        }
+        false
+    }
+
+    // 推进到第 now 个时钟周期，返回所有到期的事件
+    pub fn advance(&mut self, now: usize) -> Vec<TimerEvent> {
+        let mut events = Vec::new();
+        while self.current < now {
+            self.current += 1;
+            let current = self.current;
+            let slot = &mut self.slots[current % WHEEL_SIZE];
+            let mut i = 0;
+            while i < slot.len() {
+                if slot[i].deadline <= current {
+                    events.push(slot.swap_remove(i).event);
+                } else {
+                    i += 1;
+                }
+            }
+        }
+        events
+    }
+}
+
+lazy_static! {
+    static ref TIMER: IrqSpinLock<TimerWheel> = IrqSpinLock::new(TimerWheel::new());
+}
+
+// 在第 deadline 个时钟周期触发 event
+pub fn add_timer(deadline: usize, event: TimerEvent) -> usize {
+    TIMER.lock().add(deadline, event)
+}
+
+pub fn cancel_timer(id: usize) -> bool {
+    TIMER.lock().cancel(id)
+}
+
+// 由时钟中断调用，先释放锁再处理事件，回调中可以继续添加定时器
+pub fn tick(now: usize) {
+    let events = TIMER.lock().advance(now);
+    for event in events {
+        match event {
+            TimerEvent::Wakeup(tid) => process::wake_up(tid),
+            TimerEvent::Callback(mut callback) => callback(),
+        }
+    }
+}
@@ -0,0 +1,19 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate rust;
+
+use rust::env;
+
+// 打印命令行参数和环境变量
+#[no_mangle]
+pub fn main() -> i32 {
+    for (i, arg) in env::args().enumerate() {
+        println!("argv[{}] = {}", i, arg);
+    }
+    for (key, value) in env::vars() {
+        println!("{} = {}", key, value);
+    }
+    return 0;
+}
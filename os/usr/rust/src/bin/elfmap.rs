@@ -0,0 +1,86 @@
+#![no_std]
+#![no_main]
+#![feature(alloc)]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate rust;
+
+use alloc::vec::Vec;
+use rust::env;
+use rust::syscall::*;
+
+const PAGE_SIZE: usize = 0x1000;
+const PT_LOAD: u32 = 1;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+
+fn read_u16(data: &[u8], offset: usize) -> usize {
+    (data[offset] as usize) | (data[offset + 1] as usize) << 8
+}
+
+fn read_u32(data: &[u8], offset: usize) -> usize {
+    read_u16(data, offset) | read_u16(data, offset + 2) << 16
+}
+
+// 读出文件 path 的全部内容
+fn read_file(path: &str) -> Option<Vec<u8>> {
+    let mut cpath: Vec<u8> = path.bytes().collect();
+    cpath.push(0);
+    let fd = sys_open(cpath.as_ptr(), O_RDONLY);
+    if fd < 0 {
+        return None;
+    }
+    let mut data = Vec::new();
+    let mut buf = [0u8; 512];
+    loop {
+        let len = sys_read(fd as usize, buf.as_mut_ptr(), buf.len());
+        if len <= 0 {
+            break;
+        }
+        data.extend_from_slice(&buf[..len as usize]);
+    }
+    sys_close(fd as usize);
+    Some(data)
+}
+
+// 只读的段在第一次访问时才从文件中读入。用 lld 链接的程序的代码段通常不从页的开头开始，
+// 例如虚拟地址 0x110b4 对应文件偏移 0x10b4。检查这个程序自己的只读段在内存中的内容与文件中的相同
+#[no_mangle]
+pub fn main() -> i32 {
+    let path = env::args().next().unwrap_or("rust/elfmap");
+    let file = match read_file(path) {
+        Some(file) => file,
+        None => {
+            println!("failed to read {}", path);
+            return 1;
+        },
+    };
+    let phoff = read_u32(&file, 0x1c);
+    let phentsize = read_u16(&file, 0x2a);
+    let phnum = read_u16(&file, 0x2c);
+    let mut mid_page = false;
+    for i in 0..phnum {
+        let ph = &file[phoff + i * phentsize..];
+        let (kind, offset, vaddr, filesz, flags) =
+            (read_u32(ph, 0) as u32, read_u32(ph, 4), read_u32(ph, 8), read_u32(ph, 16), read_u32(ph, 24) as u32);
+        if kind != PT_LOAD || flags & PF_W != 0 {
+            continue;
+        }
+        println!("segment at {:#x}, offset {:#x}, size {:#x}", vaddr, offset, filesz);
+        if flags & PF_X != 0 && vaddr % PAGE_SIZE != 0 {
+            mid_page = true;
+        }
+        let memory = unsafe { core::slice::from_raw_parts(vaddr as *const u8, filesz) };
+        if memory != &file[offset..offset + filesz] {
+            println!("segment at {:#x} differs from the file", vaddr);
+            return 1;
+        }
+    }
+    if !mid_page {
+        println!("warning: the code segment starts at a page boundary");
+    }
+    println!("elfmap test passed");
+    return 0;
+}
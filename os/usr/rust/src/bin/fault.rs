@@ -0,0 +1,45 @@
+#![no_std]
+#![no_main]
+#![feature(alloc)]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate rust;
+
+use rust::syscall::{ sys_fork, sys_wait, sys_exit, sys_sleep };
+use rust::thread;
+
+// 子进程访问非法地址，应当只有子进程被结束，退出码为 128 + SIGSEGV
+// 多线程的子进程中一个线程出错时，其余线程也一起被结束
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = sys_fork();
+    if pid == 0 {
+        println!("child: writing to a null pointer");
+        unsafe { core::ptr::write_volatile(0 as *mut usize, 0); }
+        println!("child: should not reach here");
+        sys_exit(0);
+    }
+    let mut code: i32 = 0;
+    sys_wait(pid as isize, &mut code);
+    println!("child {} exited with code {}", pid, code);
+
+    let pid = sys_fork();
+    if pid == 0 {
+        let handle = thread::spawn(|| {
+            sys_sleep(100);
+            println!("child thread: writing to a null pointer");
+            unsafe { core::ptr::write_volatile(0 as *mut usize, 0); }
+        });
+        // 另一个线程出错后，join 不会返回
+        handle.join();
+        loop {
+            sys_sleep(100);
+            println!("child: should not reach here");
+        }
+    }
+    sys_wait(pid as isize, &mut code);
+    println!("multi-threaded child {} exited with code {}", pid, code);
+    return 0;
+}
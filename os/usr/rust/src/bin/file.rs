@@ -0,0 +1,32 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate rust;
+
+use rust::syscall::*;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = sys_open("test.txt\0".as_ptr(), O_RDWR | O_CREAT | O_TRUNC);
+    if fd < 0 {
+        println!("open failed: {}", fd);
+        return 1;
+    }
+    let msg = "hello file system!";
+    println!("write {} bytes to fd {}", sys_write(fd as usize, msg.as_ptr(), msg.len()), fd);
+
+    // 回到文件开头读出刚写入的内容
+    sys_lseek(fd as usize, 0, SEEK_SET);
+    let mut buf = [0u8; 32];
+    let len = sys_read(fd as usize, buf.as_mut_ptr(), buf.len());
+    println!("read {} bytes: {}", len, core::str::from_utf8(&buf[..len as usize]).unwrap());
+
+    let mut stat = Stat::default();
+    sys_fstat(fd as usize, &mut stat);
+    println!("size = {}, mode = {:#o}", stat.size, stat.mode);
+    sys_close(fd as usize);
+
+    println!("open a missing file: {}", sys_open("no_such_file\0".as_ptr(), O_RDONLY));
+    return 0;
+}
@@ -0,0 +1,27 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate rust;
+
+use rust::syscall::{ sys_fork, sys_wait };
+
+static mut COUNTER: usize = 0;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = sys_fork();
+    // 父子进程各自写同一个全局变量，写时复制保证互不影响
+    unsafe {
+        COUNTER += if pid == 0 { 1 } else { 100 };
+    }
+    if pid == 0 {
+        println!("I am child, counter = {}", unsafe { COUNTER });
+    } else {
+        println!("I am parent, child = {}, counter = {}", pid, unsafe { COUNTER });
+        let mut code: i32 = 0;
+        sys_wait(pid as isize, &mut code);
+        println!("child {} exited with code {}", pid, code);
+    }
+    return 0;
+}
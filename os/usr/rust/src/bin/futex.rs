@@ -0,0 +1,44 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate rust;
+
+use rust::syscall::*;
+use core::sync::atomic::{ AtomicI32, Ordering };
+
+// 父子进程通过共享的文件映射使用同一个 futex
+#[no_mangle]
+pub fn main() -> i32 {
+    let word = AtomicI32::new(0);
+    let timeout = TimeSpec { sec: 0, nsec: 100_000_000 };
+    let ret = sys_futex(&word as *const AtomicI32 as *const i32, FUTEX_WAIT, 0, Some(&timeout));
+    println!("wait with timeout returns {}", ret);
+    println!("wait on changed value returns {}", sys_futex(&word as *const AtomicI32 as *const i32, FUTEX_WAIT, 1, None));
+
+    let fd = sys_open("futex.txt\0".as_ptr(), O_RDWR | O_CREAT | O_TRUNC);
+    let zero = [0u8; 4];
+    sys_write(fd as usize, zero.as_ptr(), zero.len());
+    let addr = sys_mmap(0, 4, PROT_READ | PROT_WRITE, MAP_SHARED, fd as usize, 0);
+    if addr < 0 {
+        println!("mmap failed, error {}", addr);
+        return 1;
+    }
+    let word = unsafe { &*(addr as *const AtomicI32) };
+    let uaddr = addr as *const i32;
+    let pid = sys_fork();
+    if pid == 0 {
+        while word.load(Ordering::Acquire) == 0 {
+            sys_futex(uaddr, FUTEX_WAIT, 0, None);
+        }
+        println!("child woken up, word = {}", word.load(Ordering::Acquire));
+        sys_exit(0);
+    }
+    sys_sleep(100);
+    word.store(42, Ordering::Release);
+    println!("parent woke {} waiter", sys_futex(uaddr, FUTEX_WAKE, 1, None));
+    let mut code: i32 = 0;
+    sys_wait(pid as isize, &mut code);
+    sys_close(fd as usize);
+    return 0;
+}
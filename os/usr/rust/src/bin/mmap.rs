@@ -0,0 +1,86 @@
+#![no_std]
+#![no_main]
+#![feature(alloc)]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate rust;
+
+use rust::syscall::*;
+use alloc::vec::Vec;
+
+// 测试堆的增长以及匿名映射
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut v: Vec<usize> = Vec::new();
+    for i in 0..0x4000 {
+        v.push(i);
+    }
+    let sum: usize = v.iter().sum();
+    println!("heap ok, sum = {:#x}, brk = {:#x}", sum, sys_brk(0));
+
+    let len = 0x3000;
+    let addr = sys_mmap(0, len, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, 0, 0);
+    if addr < 0 {
+        println!("mmap failed, error {}", addr);
+        return 1;
+    }
+    let buf = unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, len) };
+    for (i, x) in buf.iter_mut().enumerate() {
+        *x = i as u8;
+    }
+    println!("mmap at {:#x}, buf[0x1234] = {:#x}", addr, buf[0x1234]);
+
+    // 只读之后仍然可以读
+    sys_mprotect(addr as usize, len, PROT_READ);
+    println!("after mprotect, buf[0x2345] = {:#x}", buf[0x2345]);
+
+    println!("munmap returns {}", sys_munmap(addr as usize, len));
+
+    // 长度溢出时返回错误
+    let ret = sys_mmap(0, usize::max_value(), PROT_READ, MAP_PRIVATE | MAP_ANONYMOUS, 0, 0);
+    println!("mmap with huge length returns {}", ret);
+    if ret >= 0 {
+        return 1;
+    }
+    if sys_munmap(0x1000, 0xffffefff) >= 0 {
+        println!("munmap with huge length succeeded");
+        return 1;
+    }
+
+    // 共享的文件映射，修改会写回文件
+    let fd = sys_open("mmap.txt\0".as_ptr(), O_RDWR | O_CREAT | O_TRUNC);
+    let msg = "hello mmap!";
+    sys_write(fd as usize, msg.as_ptr(), msg.len());
+    let addr = sys_mmap(0, msg.len(), PROT_READ | PROT_WRITE, MAP_SHARED, fd as usize, 0);
+    if addr < 0 {
+        println!("file mmap failed, error {}", addr);
+        return 1;
+    }
+    let buf = unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, msg.len()) };
+    buf[0] = b'H';
+    sys_msync(addr as usize, msg.len());
+    sys_munmap(addr as usize, msg.len());
+    let mut data = [0u8; 16];
+    sys_lseek(fd as usize, 0, SEEK_SET);
+    let len = sys_read(fd as usize, data.as_mut_ptr(), data.len());
+    println!("file now contains: {}", core::str::from_utf8(&data[..len as usize]).unwrap());
+    sys_close(fd as usize);
+
+    // 只读打开的文件的共享映射不能通过 mprotect 改为可写
+    let fd = sys_open("mmap.txt\0".as_ptr(), O_RDONLY);
+    let addr = sys_mmap(0, msg.len(), PROT_READ, MAP_SHARED, fd as usize, 0);
+    if addr < 0 {
+        println!("read-only file mmap failed, error {}", addr);
+        return 1;
+    }
+    let ret = sys_mprotect(addr as usize, msg.len(), PROT_READ | PROT_WRITE);
+    println!("mprotect on read-only shared mapping returns {}", ret);
+    sys_munmap(addr as usize, msg.len());
+    sys_close(fd as usize);
+    if ret >= 0 {
+        return 1;
+    }
+    return 0;
+}
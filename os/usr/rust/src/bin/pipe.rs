@@ -0,0 +1,55 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate rust;
+
+use rust::syscall::*;
+
+// 子进程向管道中写入数据后退出，父进程一直读到 EOF
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut fds = [0i32; 2];
+    if sys_pipe(&mut fds) < 0 {
+        println!("pipe failed");
+        return 1;
+    }
+    let (read, write) = (fds[0] as usize, fds[1] as usize);
+    let pid = sys_fork();
+    if pid == 0 {
+        sys_close(read);
+        for i in 0..3 {
+            let msg = "hello pipe! ";
+            sys_write(write, msg.as_ptr(), msg.len());
+            println!("child wrote message {}", i);
+        }
+        sys_close(write);
+        sys_exit(0);
+    }
+    // 父进程也要关闭写端，否则读不到 EOF
+    sys_close(write);
+    let mut buf = [0u8; 16];
+    let mut total = 0;
+    loop {
+        let len = sys_read(read, buf.as_mut_ptr(), buf.len());
+        if len <= 0 {
+            break;
+        }
+        total += len;
+        print!("{}", core::str::from_utf8(&buf[..len as usize]).unwrap());
+    }
+    println!("");
+    println!("read {} bytes before EOF", total);
+    sys_close(read);
+
+    // 读端关闭后写入会返回 EPIPE
+    sys_pipe(&mut fds);
+    sys_close(fds[0] as usize);
+    let ret = sys_write(fds[1] as usize, "x".as_ptr(), 1);
+    println!("write to closed pipe returns {}", ret);
+    sys_close(fds[1] as usize);
+
+    let mut code: i32 = 0;
+    sys_wait(pid as isize, &mut code);
+    return 0;
+}
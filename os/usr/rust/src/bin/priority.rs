@@ -0,0 +1,32 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate rust;
+
+use rust::syscall::{ sys_fork, sys_wait, sys_exit, sys_set_priority };
+
+const WORK: usize = 2000000;
+
+// 创建几个优先级不同的计算密集型子进程
+// 使用 stride 调度（bootargs="sched=stride"）时优先级高的应当先完成
+#[no_mangle]
+pub fn main() -> i32 {
+    let priorities = [1, 2, 4, 8];
+    for &priority in priorities.iter() {
+        if sys_fork() == 0 {
+            sys_set_priority(priority);
+            let mut sum: usize = 0;
+            for i in 0..WORK {
+                sum = unsafe { core::ptr::read_volatile(&sum) }.wrapping_add(i);
+            }
+            println!("priority {} done, sum = {}", priority, sum);
+            sys_exit(0);
+        }
+    }
+    for _ in priorities.iter() {
+        let mut code: i32 = 0;
+        sys_wait(-1, &mut code);
+    }
+    return 0;
+}
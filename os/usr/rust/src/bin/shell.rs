@@ -7,13 +7,93 @@ extern crate alloc;
 #[macro_use]
 extern crate rust;
 
-use rust::io::getc;
-use rust::syscall::sys_exec;
-use alloc::string::String;
+use rust::io::{ getc, STDIN, STDOUT };
+use rust::syscall::{ sys_close, sys_dup2, sys_execve, sys_exit, sys_fork, sys_pipe, sys_wait };
+use alloc::{ string::String, vec::Vec };
+use core::ptr;
 
 const LF: u8 = 0x0au8;
 const CR: u8 = 0x0du8;
 
+// 按空白分割命令，在子进程中执行，返回子进程的 pid
+// stdin 和 stdout 不为 None 时先把它们复制到 0 和 1 上
+fn run(cmd: &str, stdin: Option<usize>, stdout: Option<usize>, close: &[usize]) -> i32 {
+    let args: Vec<String> = cmd.split_whitespace().map(|arg| {
+        let mut arg = String::from(arg);
+        arg.push('\0');
+        arg
+    }).collect();
+    let pid = sys_fork();
+    if pid == 0 {
+        if let Some(fd) = stdin {
+            sys_dup2(fd, STDIN);
+        }
+        if let Some(fd) = stdout {
+            sys_dup2(fd, STDOUT);
+        }
+        for &fd in close {
+            sys_close(fd);
+        }
+        if args.is_empty() {
+            sys_exit(0);
+        }
+        let mut argv: Vec<*const u8> = args.iter().map(|arg| arg.as_ptr()).collect();
+        argv.push(ptr::null());
+        let envp: [*const u8; 1] = [ptr::null()];
+        let err = sys_execve(argv[0], argv.as_ptr(), envp.as_ptr());
+        println!("{}: failed to execute, error {}", args[0].trim_end_matches('\0'), err);
+        sys_exit(127);
+    }
+    pid
+}
+
+// 执行用 '|' 连接的一组命令，前一个命令的输出通过管道作为后一个命令的输入
+fn run_pipeline(line: &str) {
+    let cmds: Vec<&str> = line.split('|').collect();
+    let mut pids: Vec<i32> = Vec::new();
+    let mut prev_read: Option<usize> = None;
+    for (i, cmd) in cmds.iter().enumerate() {
+        let mut next: Option<(usize, usize)> = None;
+        if i + 1 < cmds.len() {
+            let mut fds = [0i32; 2];
+            let ret = sys_pipe(&mut fds);
+            if ret < 0 {
+                println!("pipe failed, error {}", ret);
+                break;
+            }
+            next = Some((fds[0] as usize, fds[1] as usize));
+        }
+        let mut close: Vec<usize> = Vec::new();
+        close.extend(prev_read);
+        if let Some((read, write)) = next {
+            close.push(read);
+            close.push(write);
+        }
+        let pid = run(cmd, prev_read, next.map(|(_, write)| write), &close);
+        if pid < 0 {
+            println!("fork failed, error {}", pid);
+        } else {
+            pids.push(pid);
+        }
+        // 父进程不再需要这些文件描述符，关闭后管道的另一端才能看到 EOF
+        if let Some(fd) = prev_read {
+            sys_close(fd);
+        }
+        prev_read = next.map(|(read, write)| {
+            sys_close(write);
+            read
+        });
+    }
+    if let Some(fd) = prev_read {
+        sys_close(fd);
+    }
+    for pid in pids {
+        let mut code: i32 = 0;
+        sys_wait(pid as isize, &mut code);
+        println!("process {} exited with code {}", pid, code);
+    }
+}
+
 // IMPORTANT: Must define main() like this
 #[no_mangle]
 pub fn main() -> i32 {
@@ -25,8 +105,8 @@ pub fn main() -> i32 {
         match c {
             LF | CR => {
                 println!("");
-                if !line.is_empty() {
-                    sys_exec(line.as_ptr());
+                if !line.trim().is_empty() {
+                    run_pipeline(&line);
                     line.clear();
                 }
                 print!(">> ");
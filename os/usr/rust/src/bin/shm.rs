@@ -0,0 +1,79 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate rust;
+
+use rust::syscall::*;
+
+const KEY: usize = 0x5348;
+const SIZE: usize = 0x3000;
+
+fn attach(id: usize) -> &'static mut [u32] {
+    let addr = sys_shmat(id, 0, 0);
+    assert!(addr > 0, "shmat failed, error {}", addr);
+    unsafe { core::slice::from_raw_parts_mut(addr as *mut u32, SIZE / 4) }
+}
+
+// 测试共享内存段：fork 之后的子进程与父进程共享已经挂载的段，
+// 另一个子进程通过键取得同一个段，挂载后写入的数据父进程可以看到
+#[no_mangle]
+pub fn main() -> i32 {
+    let id = sys_shmget(KEY, SIZE, IPC_CREAT | IPC_EXCL);
+    if id < 0 {
+        println!("shmget failed, error {}", id);
+        return 1;
+    }
+    println!("shmget again with IPC_EXCL returns {}", sys_shmget(KEY, SIZE, IPC_CREAT | IPC_EXCL));
+    let buf = attach(id as usize);
+    buf[0] = 1;
+
+    // 子进程中挂载的段与父进程使用相同的页帧，不会写时复制
+    let pid = sys_fork();
+    if pid == 0 {
+        buf[0] += 1;
+        buf[SIZE / 4 - 1] = 0x1234;
+        sys_exit(0);
+    }
+    let mut code: i32 = 0;
+    sys_wait(pid as isize, &mut code);
+    println!("after fork: buf[0] = {}, buf[last] = {:#x}", buf[0], buf[SIZE / 4 - 1]);
+
+    // 没有继承挂载的进程通过键取得段
+    let pid = sys_fork();
+    if pid == 0 {
+        sys_shmdt(buf.as_ptr() as usize);
+        let id = sys_shmget(KEY, 0, 0);
+        if id < 0 {
+            println!("child shmget failed, error {}", id);
+            sys_exit(1);
+        }
+        let buf = attach(id as usize);
+        buf[1] = buf[0] * 100;
+        sys_shmdt(buf.as_ptr() as usize);
+        sys_exit(0);
+    }
+    sys_wait(pid as isize, &mut code);
+    println!("after shmget by key: buf[1] = {}", buf[1]);
+
+    // 只读挂载的段不能通过 mprotect 改为可写
+    let addr = sys_shmat(id as usize, 0, SHM_RDONLY);
+    let ret = sys_mprotect(addr as usize, SIZE, PROT_READ | PROT_WRITE);
+    println!("mprotect on read-only attachment returns {}", ret);
+    sys_shmdt(addr as usize);
+
+    let ok = code == 0 && buf[0] == 2 && buf[1] == 200 && buf[SIZE / 4 - 1] == 0x1234 && ret < 0;
+    println!("shmdt returns {}", sys_shmdt(buf.as_ptr() as usize));
+    println!("shmdt again returns {}", sys_shmdt(buf.as_ptr() as usize));
+    // 最后一个挂载取消后段被释放，不能再通过键取得
+    let again = sys_shmget(KEY, 0, 0);
+    println!("shmget after the last shmdt returns {}", again);
+    let ok = ok && again < 0;
+    if ok {
+        println!("shm test passed");
+        0
+    } else {
+        println!("shm test failed");
+        1
+    }
+}
@@ -0,0 +1,25 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate rust;
+
+use rust::syscall::{ sys_fork, sys_wait, sys_exit, sys_sleep };
+
+// 几个子进程睡眠不同的时间，应当按照睡眠时间从短到长的顺序醒来
+#[no_mangle]
+pub fn main() -> i32 {
+    let times = [300, 100, 200];
+    for &ms in times.iter() {
+        if sys_fork() == 0 {
+            sys_sleep(ms);
+            println!("slept {} ms", ms);
+            sys_exit(0);
+        }
+    }
+    for _ in times.iter() {
+        let mut code: i32 = 0;
+        sys_wait(-1, &mut code);
+    }
+    return 0;
+}
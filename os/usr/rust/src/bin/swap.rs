@@ -0,0 +1,67 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate rust;
+
+use rust::env;
+use rust::syscall::*;
+
+const PAGE_SIZE: usize = 0x1000;
+const WORDS: usize = PAGE_SIZE / 4;
+
+// 第 i 页第 j 个字的内容
+fn pattern(i: usize, j: usize) -> u32 {
+    (i * 7919 + j) as u32
+}
+
+fn check(buf: &[u32], pages: usize) -> bool {
+    for i in 0..pages {
+        for j in 0..WORDS {
+            if buf[i * WORDS + j] != pattern(i, j) {
+                println!("page {} word {} is wrong", i, j);
+                return false;
+            }
+        }
+    }
+    true
+}
+
+// 映射一段比物理内存更大的匿名内存，写入后检查内容，测试页面的换出和换入
+// 用法：swap [MB]，默认为 16MB。例如 make run memory=32M swap=1 后在 shell 中执行 rust/swap 48
+#[no_mangle]
+pub fn main() -> i32 {
+    let mb = env::args().nth(1).and_then(|arg| arg.parse::<usize>().ok()).unwrap_or(16);
+    let len = mb << 20;
+    let pages = len / PAGE_SIZE;
+    let addr = sys_mmap(0, len, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, 0, 0);
+    if addr < 0 {
+        println!("mmap failed, error {}", addr);
+        return 1;
+    }
+    let buf = unsafe { core::slice::from_raw_parts_mut(addr as *mut u32, pages * WORDS) };
+    for i in 0..pages {
+        for j in 0..WORDS {
+            buf[i * WORDS + j] = pattern(i, j);
+        }
+    }
+    println!("wrote {} pages", pages);
+    if !check(buf, pages) {
+        return 1;
+    }
+    println!("first check ok");
+    // 子进程通过写时复制共享这段内存，其中被换出的页需要在 fork 时换入
+    let pid = sys_fork();
+    if pid == 0 {
+        sys_exit(if check(buf, pages) { 0 } else { 1 });
+    }
+    let mut code: i32 = 0;
+    sys_wait(pid as isize, &mut code);
+    if code != 0 || !check(buf, pages) {
+        println!("swap test failed");
+        return 1;
+    }
+    sys_munmap(addr as usize, len);
+    println!("swap test passed");
+    return 0;
+}
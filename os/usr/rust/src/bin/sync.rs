@@ -0,0 +1,29 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate rust;
+
+use rust::syscall::*;
+
+// 测试互斥锁和信号量的系统调用
+#[no_mangle]
+pub fn main() -> i32 {
+    let mutex = sys_mutex_create() as usize;
+    sys_mutex_lock(mutex);
+    println!("mutex {} locked", mutex);
+    println!("unlock returns {}", sys_mutex_unlock(mutex));
+    // 没有上锁时解锁会出错
+    println!("unlock again returns {}", sys_mutex_unlock(mutex));
+
+    let sem = sys_semaphore_create(0) as usize;
+    for _ in 0..3 {
+        sys_semaphore_up(sem);
+    }
+    for i in 0..3 {
+        sys_semaphore_down(sem);
+        println!("semaphore down {}", i);
+    }
+    println!("bad semaphore returns {}", sys_semaphore_down(sem + 1));
+    return 0;
+}
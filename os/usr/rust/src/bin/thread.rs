@@ -0,0 +1,35 @@
+#![no_std]
+#![no_main]
+#![feature(alloc)]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate rust;
+
+use rust::sync::Mutex;
+use rust::syscall::sys_gettid;
+use rust::thread;
+use alloc::{ sync::Arc, vec::Vec };
+
+// 多个线程共享地址空间，用 futex 实现的互斥锁保护计数器
+#[no_mangle]
+pub fn main() -> i32 {
+    let counter = Arc::new(Mutex::new(0usize));
+    let mut handles = Vec::new();
+    for i in 0..4 {
+        let counter = counter.clone();
+        handles.push(thread::spawn(move || {
+            for _ in 0..1000 {
+                *counter.lock() += 1;
+            }
+            println!("thread {} (tid {}) done", i, sys_gettid());
+        }));
+    }
+    for handle in handles {
+        let tid = handle.tid();
+        println!("thread tid {} exited with code {}", tid, handle.join());
+    }
+    println!("counter = {}", *counter.lock());
+    return 0;
+}
@@ -0,0 +1,68 @@
+use core::{ slice, str };
+
+// 由内核放在初始用户栈上的参数和环境变量，在 _start 中记录
+static mut ARGC: usize = 0;
+static mut ARGV: *const *const u8 = 0 as *const *const u8;
+static mut ENVP: *const *const u8 = 0 as *const *const u8;
+
+pub unsafe fn init(argc: usize, argv: *const *const u8, envp: *const *const u8) {
+    ARGC = argc;
+    ARGV = argv;
+    ENVP = envp;
+}
+
+unsafe fn from_cstr(s: *const u8) -> &'static str {
+    let len = (0usize..).find(|&i| *s.add(i) == 0).unwrap();
+    str::from_utf8(slice::from_raw_parts(s, len)).unwrap_or("")
+}
+
+// 命令行参数，第一个为程序路径
+pub struct Args {
+    pos: usize,
+}
+
+pub fn args() -> Args {
+    Args { pos: 0 }
+}
+
+impl Iterator for Args {
+    type Item = &'static str;
+
+    fn next(&mut self) -> Option<&'static str> {
+        unsafe {
+            if self.pos >= ARGC {
+                return None;
+            }
+            let arg = from_cstr(*ARGV.add(self.pos));
+            self.pos += 1;
+            Some(arg)
+        }
+    }
+}
+
+// 环境变量，每一项形如 "KEY=VALUE"
+pub struct Vars {
+    pos: usize,
+}
+
+pub fn vars() -> Vars {
+    Vars { pos: 0 }
+}
+
+impl Iterator for Vars {
+    type Item = (&'static str, &'static str);
+
+    fn next(&mut self) -> Option<(&'static str, &'static str)> {
+        unsafe {
+            if ENVP.is_null() || (*ENVP.add(self.pos)).is_null() {
+                return None;
+            }
+            let var = from_cstr(*ENVP.add(self.pos));
+            self.pos += 1;
+            Some(match var.find('=') {
+                Some(i) => (&var[..i], &var[i + 1..]),
+                None => (var, ""),
+            })
+        }
+    }
+}
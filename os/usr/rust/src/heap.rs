@@ -0,0 +1,46 @@
+use buddy_system_allocator::LockedHeap;
+use core::alloc::{ GlobalAlloc, Layout };
+use crate::syscall::sys_brk;
+
+// 每次至少向内核申请这么多内存
+const HEAP_GROW_SIZE: usize = 0x4000;
+
+// 堆空间不足时通过 brk 向内核申请更多内存
+pub struct Heap(LockedHeap);
+
+impl Heap {
+    pub const fn empty() -> Self {
+        Heap(LockedHeap::empty())
+    }
+
+    // 扩大堆，使其至少能分配 layout，失败时返回 false
+    fn grow(&self, layout: &Layout) -> bool {
+        // 伙伴系统只能分配按自身大小对齐的块，新增的内存需要是块大小的两倍才能保证包含一个这样的块
+        let block = layout.size().max(layout.align()).next_power_of_two();
+        let size = (block * 2).max(HEAP_GROW_SIZE);
+        let start = sys_brk(0);
+        let end = sys_brk(start + size);
+        if end < start + size {
+            return false;
+        }
+        unsafe {
+            self.0.lock().add_to_heap(start, end);
+        }
+        true
+    }
+}
+
+unsafe impl GlobalAlloc for Heap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        loop {
+            let ptr = self.0.alloc(layout);
+            if !ptr.is_null() || !self.grow(&layout) {
+                return ptr;
+            }
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.dealloc(ptr, layout)
+    }
+}
@@ -1,13 +1,15 @@
 use super::syscall;
 
+pub const STDIN: usize = 0;
+pub const STDOUT: usize = 1;
+
 pub fn putchar(ch: char) {
-    syscall::sys_write(ch as u8);
+    let mut buf = [0u8; 4];
+    puts(ch.encode_utf8(&mut buf));
 }
 
 pub fn puts(s: &str) {
-    for ch in s.chars() {
-        putchar(ch);
-    }
+    syscall::sys_write(STDOUT, s.as_ptr(), s.len());
 }
 
 #[macro_export]
@@ -38,8 +40,6 @@ pub fn _print(args: fmt::Arguments) {
     StdOut.write_fmt(args).unwrap();
 }
 
-pub const STDIN: usize = 0;
-
 pub fn getc() -> u8 {
     let mut c = 0u8;
     loop {
@@ -8,15 +8,6 @@ fn main() -> usize {
     panic!("No main() linked");
 }
 
-use crate::ALLOCATOR;
-fn init_heap() {
-    const HEAP_SIZE: usize = 0x1000;
-    static mut HEAP: [u8; HEAP_SIZE] = [0; HEAP_SIZE];
-    unsafe {
-        ALLOCATOR.lock().init(HEAP.as_ptr() as usize, HEAP_SIZE);
-    }
-}
-
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
     let location = info.location().unwrap();
@@ -32,8 +23,8 @@ fn panic(info: &PanicInfo) -> ! {
 }
 
 #[no_mangle]
-pub extern "C" fn _start(_argc: isize, _argv: *const *const u8) -> ! {
-    init_heap();
+pub extern "C" fn _start(argc: usize, argv: *const *const u8, envp: *const *const u8) -> ! {
+    unsafe { crate::env::init(argc, argv, envp); }
     sys_exit(main())
 }
 
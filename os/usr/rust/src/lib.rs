@@ -13,9 +13,10 @@ pub mod io;
 
 pub mod lang_items;
 pub mod syscall;
-
-
-use buddy_system_allocator::LockedHeap;
+pub mod env;
+pub mod heap;
+pub mod sync;
+pub mod thread;
 
 #[global_allocator]
-static ALLOCATOR: LockedHeap = LockedHeap::empty();
+static ALLOCATOR: heap::Heap = heap::Heap::empty();
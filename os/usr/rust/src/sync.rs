@@ -0,0 +1,130 @@
+use core::cell::UnsafeCell;
+use core::ops::{ Deref, DerefMut };
+use core::sync::atomic::{ AtomicI32, Ordering };
+use crate::syscall::{ sys_futex, FUTEX_WAIT, FUTEX_WAKE, TimeSpec };
+
+// 锁的状态：0 未上锁，1 已上锁且没有等待者，2 已上锁且可能有等待者
+const UNLOCKED: i32 = 0;
+const LOCKED: i32 = 1;
+const CONTENDED: i32 = 2;
+
+const ETIMEDOUT: i32 = 110;
+
+// 基于 futex 的互斥锁，没有竞争时不需要进入内核
+pub struct Mutex<T: ?Sized> {
+    state: AtomicI32,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+
+pub struct MutexGuard<'a, T: ?Sized> {
+    mutex: &'a Mutex<T>,
+}
+
+impl<T> Mutex<T> {
+    pub const fn new(data: T) -> Self {
+        Mutex {
+            state: AtomicI32::new(UNLOCKED),
+            data: UnsafeCell::new(data),
+        }
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    pub fn lock(&self) -> MutexGuard<T> {
+        if self.state.compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            // 把状态设为 CONTENDED 后睡眠，解锁的线程看到 CONTENDED 就会唤醒等待者
+            while self.state.swap(CONTENDED, Ordering::Acquire) != UNLOCKED {
+                sys_futex(self.state_ptr(), FUTEX_WAIT, CONTENDED as usize, None);
+            }
+        }
+        MutexGuard { mutex: self }
+    }
+
+    pub fn try_lock(&self) -> Option<MutexGuard<T>> {
+        if self.state.compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed).is_ok() {
+            Some(MutexGuard { mutex: self })
+        } else {
+            None
+        }
+    }
+
+    fn state_ptr(&self) -> *const i32 {
+        &self.state as *const AtomicI32 as *const i32
+    }
+
+    fn unlock(&self) {
+        if self.state.swap(UNLOCKED, Ordering::Release) == CONTENDED {
+            sys_futex(self.state_ptr(), FUTEX_WAKE, 1, None);
+        }
+    }
+}
+
+impl<'a, T: ?Sized> Deref for MutexGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for MutexGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for MutexGuard<'a, T> {
+    fn drop(&mut self) {
+        self.mutex.unlock();
+    }
+}
+
+// 基于 futex 的条件变量。seq 在每次通知时加一，等待者睡眠前记下它的值，
+// 这样在释放锁之后、睡眠之前到来的通知也不会丢失
+pub struct Condvar {
+    seq: AtomicI32,
+}
+
+impl Condvar {
+    pub const fn new() -> Self {
+        Condvar { seq: AtomicI32::new(0) }
+    }
+
+    fn seq_ptr(&self) -> *const i32 {
+        &self.seq as *const AtomicI32 as *const i32
+    }
+
+    // 释放锁并等待通知，被唤醒后重新获取锁。可能出现虚假唤醒，调用者需要重新检查条件
+    pub fn wait<'a, T: ?Sized>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        let mutex = guard.mutex;
+        let seq = self.seq.load(Ordering::Relaxed);
+        drop(guard);
+        sys_futex(self.seq_ptr(), FUTEX_WAIT, seq as usize, None);
+        mutex.lock()
+    }
+
+    // 最多等待 ms 毫秒，返回重新获取的锁以及是否超时
+    pub fn wait_timeout<'a, T: ?Sized>(&self, guard: MutexGuard<'a, T>, ms: usize) -> (MutexGuard<'a, T>, bool) {
+        let mutex = guard.mutex;
+        let seq = self.seq.load(Ordering::Relaxed);
+        drop(guard);
+        let timeout = TimeSpec {
+            sec: ms / 1000,
+            nsec: ms % 1000 * 1_000_000,
+        };
+        let ret = sys_futex(self.seq_ptr(), FUTEX_WAIT, seq as usize, Some(&timeout));
+        (mutex.lock(), ret == -ETIMEDOUT)
+    }
+
+    pub fn notify_one(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        sys_futex(self.seq_ptr(), FUTEX_WAKE, 1, None);
+    }
+
+    pub fn notify_all(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        sys_futex(self.seq_ptr(), FUTEX_WAKE, i32::max_value() as usize, None);
+    }
+}
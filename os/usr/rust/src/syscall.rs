@@ -5,39 +5,272 @@ fn sys_call(
     arg1: usize,
     arg2: usize,
     arg3: usize,
+    arg4: usize,
+    arg5: usize,
 ) -> i32 {
     let id = syscall_id as usize;
     let mut ret: i32;
     unsafe {
         asm!("ecall"
             : "={x10}" (ret)
-            : "{x17}" (id), "{x10}" (arg0), "{x11}" (arg1), "{x12}" (arg2), "{x13}" (arg3)
+            : "{x17}" (id), "{x10}" (arg0), "{x11}" (arg1), "{x12}" (arg2), "{x13}" (arg3), "{x14}" (arg4), "{x15}" (arg5)
             : "memory"
             : "volatile");
     }
     ret
 }
 
-pub fn sys_write(ch : u8) -> i32 {
-    sys_call(SyscallId::Write, ch as usize, 0, 0, 0)
+pub fn sys_write(fd : usize, base : *const u8, len : usize) -> i32 {
+    sys_call(SyscallId::Write, fd, base as usize, len, 0, 0, 0)
 }
 
 pub fn sys_exit(code: usize) -> ! {
-    sys_call(SyscallId::Exit, code, 0, 0, 0);
+    sys_call(SyscallId::Exit, code, 0, 0, 0, 0, 0);
     loop{}
 }
 
 pub fn sys_read(fd : usize, base : *const u8, len : usize) -> i32 {
-    sys_call(SyscallId::Read, fd, base as usize , len , 0)
+    sys_call(SyscallId::Read, fd, base as usize , len , 0, 0, 0)
 }
 
-pub fn sys_exec(path : *const u8) {
-    sys_call(SyscallId::Exec, path as usize, 0, 0, 0);
+pub const AT_FDCWD: isize = -100;
+
+pub const O_RDONLY: usize = 0;
+pub const O_WRONLY: usize = 1;
+pub const O_RDWR: usize = 2;
+pub const O_CREAT: usize = 0x40;
+pub const O_TRUNC: usize = 0x200;
+
+pub const SEEK_SET: u8 = 0;
+pub const SEEK_CUR: u8 = 1;
+pub const SEEK_END: u8 = 2;
+
+// 与内核中 fstat 写入的结构体布局相同
+#[repr(C)]
+#[derive(Default)]
+pub struct Stat {
+    pub dev: u64,
+    pub ino: u64,
+    pub mode: u32,
+    pub nlink: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    pub blksize: u32,
+    pub blocks: u64,
+    pub atime: u64,
+    pub mtime: u64,
+    pub ctime: u64,
+}
+
+// path 需要以 '\0' 结尾，出错时返回负的错误码
+pub fn sys_open(path : *const u8, flags : usize) -> i32 {
+    sys_call(SyscallId::Openat, AT_FDCWD as usize, path as usize, flags, 0, 0, 0)
+}
+
+pub fn sys_close(fd : usize) -> i32 {
+    sys_call(SyscallId::Close, fd, 0, 0, 0, 0, 0)
+}
+
+pub fn sys_lseek(fd : usize, offset : isize, whence : u8) -> i32 {
+    sys_call(SyscallId::Lseek, fd, offset as usize, whence as usize, 0, 0, 0)
+}
+
+pub fn sys_fstat(fd : usize, stat : *mut Stat) -> i32 {
+    sys_call(SyscallId::Fstat, fd, stat as usize, 0, 0, 0, 0)
+}
+
+// 设置当前线程的优先级，只对 stride 调度算法有效
+pub fn sys_set_priority(priority : usize) -> i32 {
+    sys_call(SyscallId::SetPriority, priority, 0, 0, 0, 0, 0)
+}
+
+#[repr(C)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+pub fn sys_nanosleep(req : &TimeSpec) -> i32 {
+    sys_call(SyscallId::Nanosleep, req as *const TimeSpec as usize, 0, 0, 0, 0, 0)
+}
+
+pub const FUTEX_WAIT: usize = 0;
+pub const FUTEX_WAKE: usize = 1;
+
+// FUTEX_WAIT：*uaddr 等于 val 时睡眠直到被唤醒，timeout 不为空时最多等待这么长时间
+// FUTEX_WAKE：唤醒至多 val 个在 uaddr 上等待的线程，返回唤醒的数量
+pub fn sys_futex(uaddr : *const i32, op : usize, val : usize, timeout : Option<&TimeSpec>) -> i32 {
+    let timeout = timeout.map_or(0, |t| t as *const TimeSpec as usize);
+    sys_call(SyscallId::Futex, uaddr as usize, op, val, timeout, 0, 0)
+}
+
+// 睡眠 ms 毫秒
+pub fn sys_sleep(ms : usize) -> i32 {
+    let req = TimeSpec {
+        sec: ms / 1000,
+        nsec: ms % 1000 * 1_000_000,
+    };
+    sys_nanosleep(&req)
+}
+
+pub fn sys_fork() -> i32 {
+    sys_call(SyscallId::Clone, 0, 0, 0, 0, 0, 0)
+}
+
+pub const CLONE_VM: usize = 0x100;
+
+// 带有 CLONE_VM 时创建与当前线程共享地址空间的线程，新线程在 newsp 指向的栈上返回 0
+// 返回后不能再使用原来栈上的数据，一般应使用 sys_thread_create
+pub fn sys_clone(flags : usize, newsp : usize) -> i32 {
+    sys_call(SyscallId::Clone, flags, newsp, 0, 0, 0, 0)
+}
+
+// 创建一个从 entry 开始执行的线程，stack 为栈顶，arg 作为 entry 的参数，返回新线程的 tid
+pub fn sys_thread_create(entry : usize, stack : usize, arg : usize) -> i32 {
+    sys_call(SyscallId::ThreadCreate, entry, stack, arg, 0, 0, 0)
+}
+
+// 等待同一进程中的线程 tid 退出，退出码写入 code 指向的位置
+pub fn sys_thread_join(tid : usize, code : *mut i32) -> i32 {
+    sys_call(SyscallId::ThreadJoin, tid, code as usize, 0, 0, 0, 0)
+}
+
+pub fn sys_gettid() -> i32 {
+    sys_call(SyscallId::Gettid, 0, 0, 0, 0, 0, 0)
+}
+
+// 用 path 指向的程序替换当前进程，argv 和 envp 是以空指针结尾的字符串指针数组
+// 成功时不会返回
+pub fn sys_execve(path : *const u8, argv : *const *const u8, envp : *const *const u8) -> i32 {
+    sys_call(SyscallId::Execve, path as usize, argv as usize, envp as usize, 0, 0, 0)
+}
+
+// pid 为 -1 时等待任意子进程
+pub fn sys_wait(pid : isize, code : *mut i32) -> i32 {
+    sys_call(SyscallId::Wait4, pid as usize, code as usize, 0, 0, 0, 0)
+}
+
+// 设置 brk，返回新的 brk，失败时返回原来的值。addr 为 0 时只查询当前的 brk
+pub fn sys_brk(addr : usize) -> usize {
+    sys_call(SyscallId::Brk, addr, 0, 0, 0, 0, 0) as usize
+}
+
+pub const PROT_NONE: usize = 0;
+pub const PROT_READ: usize = 1;
+pub const PROT_WRITE: usize = 2;
+pub const PROT_EXEC: usize = 4;
+
+pub const MAP_SHARED: usize = 0x01;
+pub const MAP_PRIVATE: usize = 0x02;
+pub const MAP_FIXED: usize = 0x10;
+pub const MAP_ANONYMOUS: usize = 0x20;
+
+// 成功时返回映射的起始地址，出错时返回负的错误码
+pub fn sys_mmap(addr : usize, len : usize, prot : usize, flags : usize, fd : usize, offset : usize) -> isize {
+    sys_call(SyscallId::Mmap, addr, len, prot, flags, fd, offset) as isize
+}
+
+pub fn sys_munmap(addr : usize, len : usize) -> i32 {
+    sys_call(SyscallId::Munmap, addr, len, 0, 0, 0, 0)
+}
+
+pub fn sys_mprotect(addr : usize, len : usize, prot : usize) -> i32 {
+    sys_call(SyscallId::Mprotect, addr, len, prot, 0, 0, 0)
+}
+
+// 把共享文件映射的修改写回文件
+pub fn sys_msync(addr : usize, len : usize) -> i32 {
+    sys_call(SyscallId::Msync, addr, len, 0, 0, 0, 0)
+}
+
+pub const IPC_PRIVATE: usize = 0;
+pub const IPC_CREAT: usize = 0o1000;
+pub const IPC_EXCL: usize = 0o2000;
+pub const SHM_RDONLY: usize = 0o10000;
+
+// 取得键为 key 的共享内存段，返回其编号。key 为 IPC_PRIVATE 时总是创建新的段
+pub fn sys_shmget(key : usize, size : usize, flags : usize) -> i32 {
+    sys_call(SyscallId::Shmget, key, size, flags, 0, 0, 0)
+}
+
+// 挂载共享内存段，addr 为 0 时由内核选择地址。成功时返回挂载的起始地址，出错时返回负的错误码
+pub fn sys_shmat(id : usize, addr : usize, flags : usize) -> isize {
+    sys_call(SyscallId::Shmat, id, addr, flags, 0, 0, 0) as isize
+}
+
+pub fn sys_shmdt(addr : usize) -> i32 {
+    sys_call(SyscallId::Shmdt, addr, 0, 0, 0, 0, 0)
+}
+
+// 创建管道，fds[0] 为读端，fds[1] 为写端
+pub fn sys_pipe(fds : &mut [i32; 2]) -> i32 {
+    sys_call(SyscallId::Pipe2, fds.as_mut_ptr() as usize, 0, 0, 0, 0, 0)
+}
+
+// 让 newfd 指向与 oldfd 相同的文件，newfd 原来打开的文件会被关闭
+pub fn sys_dup2(oldfd : usize, newfd : usize) -> i32 {
+    sys_call(SyscallId::Dup3, oldfd, newfd, 0, 0, 0, 0)
+}
+
+// 创建互斥锁，返回其编号
+pub fn sys_mutex_create() -> i32 {
+    sys_call(SyscallId::MutexCreate, 0, 0, 0, 0, 0, 0)
+}
+
+pub fn sys_mutex_lock(id : usize) -> i32 {
+    sys_call(SyscallId::MutexLock, id, 0, 0, 0, 0, 0)
+}
+
+pub fn sys_mutex_unlock(id : usize) -> i32 {
+    sys_call(SyscallId::MutexUnlock, id, 0, 0, 0, 0, 0)
+}
+
+// 创建初始计数为 count 的信号量，返回其编号
+pub fn sys_semaphore_create(count : usize) -> i32 {
+    sys_call(SyscallId::SemaphoreCreate, count, 0, 0, 0, 0, 0)
+}
+
+pub fn sys_semaphore_up(id : usize) -> i32 {
+    sys_call(SyscallId::SemaphoreUp, id, 0, 0, 0, 0, 0)
+}
+
+// 计数为 0 时阻塞
+pub fn sys_semaphore_down(id : usize) -> i32 {
+    sys_call(SyscallId::SemaphoreDown, id, 0, 0, 0, 0, 0)
 }
 
 enum SyscallId {
+    Dup3 = 24,
+    Openat = 56,
+    Close = 57,
+    Pipe2 = 59,
+    Lseek = 62,
     Read = 63,
     Write = 64,
+    Fstat = 80,
     Exit = 93,
-    Exec = 221,
+    Futex = 98,
+    Nanosleep = 101,
+    SetPriority = 140,
+    Gettid = 178,
+    Shmget = 194,
+    Shmat = 196,
+    Shmdt = 197,
+    Brk = 214,
+    Munmap = 215,
+    Clone = 220,
+    Execve = 221,
+    Mmap = 222,
+    Mprotect = 226,
+    Msync = 227,
+    Wait4 = 260,
+    ThreadCreate = 1000,
+    ThreadJoin = 1002,
+    MutexCreate = 1010,
+    MutexLock = 1011,
+    MutexUnlock = 1012,
+    SemaphoreCreate = 1020,
+    SemaphoreUp = 1021,
+    SemaphoreDown = 1022,
 }
\ No newline at end of file
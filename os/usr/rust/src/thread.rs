@@ -0,0 +1,55 @@
+use alloc::{ boxed::Box, vec::Vec };
+use core::mem;
+use crate::syscall::{ sys_exit, sys_thread_create, sys_thread_join };
+
+// 每个线程的用户栈大小
+const THREAD_STACK_SIZE: usize = 0x4000;
+
+pub struct JoinHandle {
+    tid: usize,
+    stack: Option<Vec<u8>>,
+}
+
+impl JoinHandle {
+    pub fn tid(&self) -> usize {
+        self.tid
+    }
+
+    // 等待线程结束，返回其退出码
+    pub fn join(mut self) -> i32 {
+        let mut code: i32 = 0;
+        sys_thread_join(self.tid, &mut code);
+        // 线程已经结束，可以释放它的栈了
+        self.stack.take();
+        code
+    }
+}
+
+impl Drop for JoinHandle {
+    // 没有 join 的线程可能还在运行，它的栈不能释放
+    fn drop(&mut self) {
+        if let Some(stack) = self.stack.take() {
+            mem::forget(stack);
+        }
+    }
+}
+
+// 新线程的入口，arg 是 spawn 中放到堆上的闭包
+extern "C" fn thread_start<F: FnOnce() + Send + 'static>(arg: usize) -> ! {
+    let f = unsafe { Box::from_raw(arg as *mut F) };
+    (*f)();
+    sys_exit(0)
+}
+
+// 创建一个线程执行 f，栈从堆上分配
+pub fn spawn<F: FnOnce() + Send + 'static>(f: F) -> JoinHandle {
+    let mut stack: Vec<u8> = Vec::with_capacity(THREAD_STACK_SIZE);
+    let stack_top = (stack.as_mut_ptr() as usize + THREAD_STACK_SIZE) & !0xf;
+    let arg = Box::into_raw(Box::new(f)) as usize;
+    let tid = sys_thread_create(thread_start::<F> as usize, stack_top, arg);
+    if tid < 0 {
+        unsafe { drop(Box::from_raw(arg as *mut F)); }
+        panic!("failed to create thread, error {}", tid);
+    }
+    JoinHandle { tid: tid as usize, stack: Some(stack) }
+}